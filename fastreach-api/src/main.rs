@@ -3,14 +3,24 @@ use std::{
     sync::{Arc, LazyLock},
 };
 
-use chrono::{DateTime, Duration};
+use chrono::{DateTime, Duration, NaiveDateTime, Offset, TimeZone};
+use chrono_tz::Tz;
 use fastreach_core::{
-    cascade,
-    graph::{Graph, IsochroneDijsktra},
+    cascade::Isochrone,
+    graph::{
+        Direction, Graph, IsochroneDijsktra, NodeTree, SearchOptions, Seed, TimedNode, Walking,
+    },
+    header::Metadata,
+    matrix::Matrix,
+    profile::Profile,
+    route::{Leg, LegMode},
+    workspace::PooledSearch,
 };
-use geo::{ChamberlainDuquetteArea, Polygon};
+use geo::Polygon;
 use memmap2::Mmap;
+use serde::{de::DeserializeOwned, Serialize};
 use thiserror::Error;
+use tokio::sync::Semaphore;
 use warp::{http::StatusCode, reply, Filter};
 
 mod filters;
@@ -19,6 +29,13 @@ const GRAPH_DEFAULT: &str = "graph.bin";
 const STATIC_DEFAULT: &str = "static";
 const MAX_MINUTES_DEFAULT: i64 = 120;
 const PARALLEL_DEFAULT: usize = 2;
+const MAX_BANDS: usize = 12;
+const MAX_PROFILE_DEPARTURES: i64 = 60;
+const MAX_MATRIX_ORIGINS: usize = 50;
+const MAX_MATRIX_CELLS: usize = 5000;
+const MAX_CHANGE_MINUTES: i64 = 30;
+const WALK_SPEED_RANGE: std::ops::RangeInclusive<f32> = 1.0..=10.0; // in km/h
+const MAX_WALK_LIMIT: f32 = 5000.0; // in m
 
 static GRAPH_DATA: LazyLock<Mmap> = LazyLock::new(|| {
     let path = std::env::var("FASTREACH_GRAPH").unwrap_or_else(|_| GRAPH_DEFAULT.to_owned());
@@ -26,12 +43,147 @@ static GRAPH_DATA: LazyLock<Mmap> = LazyLock::new(|| {
     unsafe { Mmap::map(&file).expect("failed mmap") }
 });
 
+static GRAPH: LazyLock<Graph<'static>> = LazyLock::new(|| {
+    if Metadata::has_header(&GRAPH_DATA) {
+        Graph::from_slice(&GRAPH_DATA).expect("failed to parse graph")
+    } else {
+        println!("Graph has no header, reading it as netex-parse output");
+        Graph::from_legacy_slice(&GRAPH_DATA).expect("failed to parse graph")
+    }
+});
+
+/// Search settings shared by all requests.
+#[derive(serde_derive::Deserialize)]
+struct RoutingBody {
+    /// interpret start as the latest arrival time
+    #[serde(default)]
+    arrive_by: bool,
+    /// minimum minutes to change between vehicles
+    #[serde(default)]
+    min_change: i64,
+    /// minutes added for every boarded vehicle
+    #[serde(default)]
+    boarding_penalty: i64,
+    /// maximum number of changes between vehicles
+    max_transfers: Option<u8>,
+    /// IANA name of the time zone of the timetable, which graphs without header lack.
+    /// Graphs with header only accept their own time zone.
+    timezone: Option<String>,
+    /// walking speed in km/h
+    walk_speed: Option<f32>,
+    /// maximum distance in m walked from a reached station
+    max_walk: Option<f32>,
+}
+
 #[derive(serde_derive::Deserialize)]
 struct IsochroneBody {
     // JS cannot deal with large integers in JSON
-    id: String,
+    id: Option<String>,
+    /// origin used instead of a station when no id is given
+    lat: Option<f32>,
+    lon: Option<f32>,
+    start: i64,
+    minutes: i64,
+    #[serde(flatten)]
+    routing: RoutingBody,
+}
+
+#[derive(serde_derive::Deserialize)]
+struct MultiIsochroneBody {
+    ids: Vec<String>,
+    /// minutes already spent before reaching the station with the same index
+    #[serde(default)]
+    offsets: Vec<i64>,
+    start: i64,
+    minutes: i64,
+    #[serde(flatten)]
+    routing: RoutingBody,
+}
+
+#[derive(serde_derive::Deserialize)]
+struct BandsBody {
+    id: Option<String>,
+    lat: Option<f32>,
+    lon: Option<f32>,
+    start: i64,
+    /// duration of each band
+    minutes: Vec<i64>,
+    #[serde(flatten)]
+    routing: RoutingBody,
+}
+
+#[derive(serde_derive::Deserialize)]
+struct ProfileBody {
+    id: Option<String>,
+    lat: Option<f32>,
+    lon: Option<f32>,
+    /// first departure of the window
+    start: i64,
+    /// length of the departure window in minutes
+    window: i64,
+    /// minutes between departures
+    step: i64,
+    minutes: i64,
+    /// share of departures, which must reach a station, for each returned isochrone
+    shares: Vec<f64>,
+    #[serde(flatten)]
+    routing: RoutingBody,
+}
+
+#[derive(serde_derive::Deserialize)]
+struct RouteBody {
+    id: Option<String>,
+    lat: Option<f32>,
+    lon: Option<f32>,
+    /// reached station, whose route is returned
+    station: String,
+    start: i64,
+    minutes: i64,
+    #[serde(flatten)]
+    routing: RoutingBody,
+}
+
+#[derive(serde_derive::Deserialize)]
+struct MatrixBody {
+    origins: Vec<String>,
+    destinations: Vec<String>,
     start: i64,
+    /// destinations further away are unreachable
     minutes: i64,
+    #[serde(flatten)]
+    routing: RoutingBody,
+}
+
+#[derive(serde_derive::Serialize)]
+struct StopReply {
+    id: String,
+    name: String,
+}
+
+#[derive(serde_derive::Serialize)]
+struct LegReply {
+    /// either "walk" or "ride"
+    mode: &'static str,
+    trip: Option<u32>,
+    from: StopReply,
+    to: StopReply,
+    departure: i64,
+    arrival: i64,
+    stops: Vec<StopReply>,
+}
+
+#[derive(serde_derive::Serialize)]
+struct RouteReply {
+    /// minutes spent before the first or after the last leg
+    offset: i64,
+    legs: Vec<LegReply>,
+}
+
+/// Rows per origin with a column per destination, `None` if unreachable.
+#[derive(serde_derive::Serialize)]
+struct MatrixReply {
+    minutes: Vec<Vec<Option<i64>>>,
+    arrivals: Vec<Vec<Option<i64>>>,
 }
 
 #[derive(serde_derive::Serialize)]
@@ -49,45 +201,489 @@ pub enum HandlerError {
     InternalServerError(String),
 }
 
-struct IsochroneHandler<'a> {
-    graph: Graph<'a>,
+struct IsochroneHandler {
+    graph: &'static Graph<'static>,
+    tree: NodeTree<'static, 'static>,
     max_minutes: i64,
 }
 
-impl IsochroneHandler<'_> {
-    fn handle_isochrone(&self, body: &IsochroneBody) -> Result<IsochroneReply, HandlerError> {
-        if body.minutes < 0 || body.minutes > self.max_minutes {
-            return Err(HandlerError::BadRequest("minutes out of range".to_owned()));
-        }
-        let id = str::parse::<u64>(&body.id)
+impl IsochroneHandler {
+    fn parse_station(&self, id: &str) -> Result<usize, HandlerError> {
+        let id = str::parse::<u64>(id)
             .map_err(|_| HandlerError::BadRequest("cannot parse id".to_owned()))?;
-        let start_idx = self
-            .graph
+        self.graph
             .ids
             .get(&id)
-            .ok_or(HandlerError::BadRequest("station not found".to_owned()))?;
-        let start_time = DateTime::from_timestamp_millis(body.start)
+            .copied()
+            .ok_or(HandlerError::BadRequest("station not found".to_owned()))
+    }
+
+    fn parse_minutes(&self, minutes: i64) -> Result<Duration, HandlerError> {
+        if minutes < 0 || minutes > self.max_minutes {
+            return Err(HandlerError::BadRequest("minutes out of range".to_owned()));
+        }
+        Ok(Duration::minutes(minutes))
+    }
+
+    /// Returns the time zone of the timetable, which is either stored in the graph
+    /// or given by the request for graphs without header and UTC otherwise.
+    fn timetable_zone(
+        metadata: Option<&Metadata>,
+        requested: Option<&str>,
+    ) -> Result<Tz, HandlerError> {
+        let Some(name) = requested else {
+            return Ok(metadata.map_or(chrono_tz::UTC, |m| m.timezone));
+        };
+        let timezone: Tz = name
+            .parse()
+            .map_err(|_| HandlerError::BadRequest("unknown time zone".to_owned()))?;
+        if metadata.is_some_and(|m| m.timezone != timezone) {
+            return Err(HandlerError::BadRequest(
+                "time zone differs from the graph".to_owned(),
+            ));
+        }
+        Ok(timezone)
+    }
+
+    fn parse_timezone(&self, routing: &RoutingBody) -> Result<Tz, HandlerError> {
+        Self::timetable_zone(self.graph.metadata(), routing.timezone.as_deref())
+    }
+
+    /// Converts the timestamp into the local time of the timetable.
+    fn parse_start(start: i64, timezone: Tz) -> Result<NaiveDateTime, HandlerError> {
+        let start_time = DateTime::from_timestamp_millis(start)
             .ok_or(HandlerError::BadRequest("invalid start time".to_owned()))?;
-        let mut algo = IsochroneDijsktra::new(&self.graph);
+        Ok(start_time.with_timezone(&timezone).naive_local())
+    }
+
+    /// Converts a local time of the timetable into a timestamp.
+    fn to_millis(time: NaiveDateTime, timezone: Tz) -> i64 {
+        if let Some(instant) = timezone.from_local_datetime(&time).earliest() {
+            return instant.timestamp_millis();
+        }
+        // local times skipped by a DST transition are moved forward by the gap
+        // using the offset of the previous day
+        let offset = timezone
+            .offset_from_utc_datetime(&(time - Duration::days(1)))
+            .fix();
+        (time - Duration::seconds(offset.local_minus_utc().into()))
+            .and_utc()
+            .timestamp_millis()
+    }
+
+    /// Returns the seeds for either a station id or an origin coordinate.
+    /// The origin is returned as well, if given.
+    fn parse_seeds(
+        &self,
+        id: Option<&str>,
+        lat: Option<f32>,
+        lon: Option<f32>,
+        duration: Duration,
+        walking: &Walking,
+    ) -> Result<(Vec<Seed>, Option<geo::Point<f32>>), HandlerError> {
+        if let Some(id) = id {
+            return Ok((vec![Seed::new(self.parse_station(id)?)], None));
+        }
+        let (Some(lat), Some(lon)) = (lat, lon) else {
+            return Err(HandlerError::BadRequest("missing id or origin".to_owned()));
+        };
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+            return Err(HandlerError::BadRequest("origin out of range".to_owned()));
+        }
+        let origin = geo::Point::new(lon, lat);
+        Ok((
+            self.tree.seeds_around(origin, duration, walking),
+            Some(origin),
+        ))
+    }
+
+    fn parse_walking(routing: &RoutingBody) -> Result<Walking, HandlerError> {
+        let mut walking = Walking::default();
+        if let Some(speed) = routing.walk_speed {
+            if !WALK_SPEED_RANGE.contains(&speed) {
+                return Err(HandlerError::BadRequest(
+                    "walk speed out of range".to_owned(),
+                ));
+            }
+            // km/h to m/min
+            walking.speed = speed * 1000.0 / 60.0;
+        }
+        if let Some(max_walk) = routing.max_walk {
+            if !(0.0..=MAX_WALK_LIMIT).contains(&max_walk) {
+                return Err(HandlerError::BadRequest("max walk out of range".to_owned()));
+            }
+            walking.max_radius = Some(max_walk);
+        }
+        Ok(walking)
+    }
+
+    fn parse_options(&self, routing: &RoutingBody) -> Result<SearchOptions, HandlerError> {
+        if !(0..=MAX_CHANGE_MINUTES).contains(&routing.min_change)
+            || !(0..=MAX_CHANGE_MINUTES).contains(&routing.boarding_penalty)
+        {
+            return Err(HandlerError::BadRequest(
+                "transfer minutes out of range".to_owned(),
+            ));
+        }
+        let transfer_rules = routing.min_change > 0
+            || routing.boarding_penalty > 0
+            || routing.max_transfers.is_some();
+        if transfer_rules && !self.graph.has_trips() {
+            return Err(HandlerError::BadRequest(
+                "transfer rules are not supported by this graph".to_owned(),
+            ));
+        }
+        Ok(SearchOptions {
+            min_change: Duration::minutes(routing.min_change),
+            boarding_penalty: Duration::minutes(routing.boarding_penalty),
+            max_transfers: routing.max_transfers,
+            walking: Self::parse_walking(routing)?,
+            ..SearchOptions::default()
+        })
+    }
+
+    fn algo(&self, routing: &RoutingBody) -> Result<PooledSearch<'static, 'static>, HandlerError> {
+        let direction = if routing.arrive_by {
+            Direction::Reverse
+        } else {
+            Direction::Forward
+        };
+        Ok(PooledSearch::new(
+            IsochroneDijsktra::new(self.graph)
+                .with_direction(direction)
+                .with_options(self.parse_options(routing)?),
+        ))
+    }
+
+    fn to_isochrone(
+        reached: &[TimedNode],
+        origin: Option<geo::Point<f32>>,
+        duration: Duration,
+        walking: &Walking,
+    ) -> Isochrone {
+        let mut polys: Vec<Polygon<f32>> = reached.iter().map(TimedNode::to_poly).collect();
+        if let Some(origin) = origin {
+            polys.push(walking.poly(origin, duration));
+        }
+        Isochrone::from_polys(polys)
+    }
+
+    fn handle_isochrone(&self, body: &IsochroneBody) -> Result<IsochroneReply, HandlerError> {
+        let duration = self.parse_minutes(body.minutes)?;
+        let timezone = self.parse_timezone(&body.routing)?;
+        let start = Self::parse_start(body.start, timezone)?;
+        let mut algo = self.algo(&body.routing)?;
+        let walking = algo.options().walking;
+        let (seeds, origin) =
+            self.parse_seeds(body.id.as_deref(), body.lat, body.lon, duration, &walking)?;
+        let reached = algo
+            .nodes_within_multi(&seeds, start, duration)
+            .map_err(|_| HandlerError::InternalServerError("failed dijsktra".to_owned()))?;
+        Ok(Self::to_isochrone(&reached, origin, duration, &walking).into())
+    }
+
+    fn handle_multi_isochrone(
+        &self,
+        body: &MultiIsochroneBody,
+    ) -> Result<IsochroneReply, HandlerError> {
+        let duration = self.parse_minutes(body.minutes)?;
+        let timezone = self.parse_timezone(&body.routing)?;
+        let start = Self::parse_start(body.start, timezone)?;
+        if body.ids.is_empty() {
+            return Err(HandlerError::BadRequest("no stations given".to_owned()));
+        }
+        if !body.offsets.is_empty() && body.offsets.len() != body.ids.len() {
+            return Err(HandlerError::BadRequest(
+                "offsets do not match stations".to_owned(),
+            ));
+        }
+        let mut seeds = Vec::with_capacity(body.ids.len());
+        for (i, id) in body.ids.iter().enumerate() {
+            let offset = body.offsets.get(i).copied().unwrap_or(0);
+            if offset < 0 || offset > body.minutes {
+                return Err(HandlerError::BadRequest("offset out of range".to_owned()));
+            }
+            seeds.push(Seed::with_offset(
+                self.parse_station(id)?,
+                Duration::minutes(offset),
+            ));
+        }
+        let reached = self
+            .algo(&body.routing)?
+            .nodes_within_multi(&seeds, start, duration)
+            .map_err(|_| HandlerError::InternalServerError("failed dijsktra".to_owned()))?;
+        Ok(Self::to_isochrone(&reached, None, duration, &Walking::default()).into())
+    }
+
+    fn handle_bands(&self, body: &BandsBody) -> Result<geojson::GeoJson, HandlerError> {
+        if body.minutes.is_empty() || body.minutes.len() > MAX_BANDS {
+            return Err(HandlerError::BadRequest(
+                "invalid number of bands".to_owned(),
+            ));
+        }
+        let mut bands = body
+            .minutes
+            .iter()
+            .map(|m| self.parse_minutes(*m))
+            .collect::<Result<Vec<_>, _>>()?;
+        bands.sort();
+        bands.dedup();
+        let max = *bands.last().unwrap_or(&Duration::zero());
+        let timezone = self.parse_timezone(&body.routing)?;
+        let start = Self::parse_start(body.start, timezone)?;
+        let mut algo = self.algo(&body.routing)?;
+        let walking = algo.options().walking;
+        let (seeds, origin) =
+            self.parse_seeds(body.id.as_deref(), body.lat, body.lon, max, &walking)?;
         let reached = algo
-            .nodes_within(
-                *start_idx,
-                start_time.naive_utc(),
-                Duration::minutes(body.minutes),
-            )
+            .nodes_within_bands(&seeds, start, &bands)
             .map_err(|_| HandlerError::InternalServerError("failed dijsktra".to_owned()))?;
+        let features = bands
+            .iter()
+            .zip(reached)
+            .map(|(band, nodes)| {
+                let isochrone = Self::to_isochrone(&nodes, origin, *band, &walking);
+                let mut properties = geojson::JsonObject::new();
+                properties.insert("minutes".to_owned(), band.num_minutes().into());
+                properties.insert("area".to_owned(), isochrone.area.into());
+                properties.insert("diameter".to_owned(), isochrone.diameter.into());
+                geojson::Feature {
+                    bbox: None,
+                    geometry: Some(geojson::Geometry::from(&isochrone.polygon)),
+                    id: None,
+                    properties: Some(properties),
+                    foreign_members: None,
+                }
+            })
+            .collect();
+        Ok(geojson::GeoJson::FeatureCollection(
+            geojson::FeatureCollection {
+                bbox: None,
+                features,
+                foreign_members: None,
+            },
+        ))
+    }
+}
 
-        let polys: Vec<Polygon<f32>> = reached.into_iter().map(|n| n.to_poly()).collect();
-        let merged = cascade::union_polys(polys);
+impl IsochroneHandler {
+    fn handle_profile(&self, body: &ProfileBody) -> Result<geojson::GeoJson, HandlerError> {
+        if body.shares.is_empty()
+            || body.shares.len() > MAX_BANDS
+            || body.shares.iter().any(|share| !(0.0..=1.0).contains(share))
+        {
+            return Err(HandlerError::BadRequest("invalid shares".to_owned()));
+        }
+        if body.step <= 0 || body.window <= 0 || body.window / body.step > MAX_PROFILE_DEPARTURES {
+            return Err(HandlerError::BadRequest(
+                "invalid departure window".to_owned(),
+            ));
+        }
+        let duration = self.parse_minutes(body.minutes)?;
+        let timezone = self.parse_timezone(&body.routing)?;
+        let start = Self::parse_start(body.start, timezone)?;
+        let mut algo = self.algo(&body.routing)?;
+        let walking = algo.options().walking;
+        let (seeds, origin) =
+            self.parse_seeds(body.id.as_deref(), body.lat, body.lon, duration, &walking)?;
+        let profile = Profile::compute(
+            &mut algo,
+            &seeds,
+            start,
+            start + Duration::minutes(body.window),
+            Duration::minutes(body.step),
+            duration,
+        )
+        .map_err(|_| HandlerError::InternalServerError("failed dijsktra".to_owned()))?;
+        let features = body
+            .shares
+            .iter()
+            .map(|share| {
+                #[allow(clippy::cast_possible_truncation)]
+                let nodes = profile.nodes_reachable_in(*share as f32);
+                let mut properties = geojson::JsonObject::new();
+                properties.insert("share".to_owned(), (*share).into());
+                // without any reached station or origin there is no area
+                let geometry = if nodes.is_empty() && origin.is_none() {
+                    properties.insert("area".to_owned(), 0.0.into());
+                    properties.insert("diameter".to_owned(), 0.0.into());
+                    None
+                } else {
+                    let isochrone = Self::to_isochrone(&nodes, origin, duration, &walking);
+                    properties.insert("area".to_owned(), isochrone.area.into());
+                    properties.insert("diameter".to_owned(), isochrone.diameter.into());
+                    Some(geojson::Geometry::from(&isochrone.polygon))
+                };
+                geojson::Feature {
+                    bbox: None,
+                    geometry,
+                    id: None,
+                    properties: Some(properties),
+                    foreign_members: None,
+                }
+            })
+            .collect();
+        Ok(geojson::GeoJson::FeatureCollection(
+            geojson::FeatureCollection {
+                bbox: None,
+                features,
+                foreign_members: None,
+            },
+        ))
+    }
+}
+
+impl IsochroneHandler {
+    fn to_stop(&self, idx: usize) -> Result<StopReply, HandlerError> {
+        let node = &self.graph.nodes[idx];
+        let name = node
+            .name()
+            .map_err(|_| HandlerError::InternalServerError("invalid station name".to_owned()))?;
+        Ok(StopReply {
+            id: node.id().to_string(),
+            name: name.to_owned(),
+        })
+    }
+
+    fn to_leg(&self, leg: &Leg, timezone: Tz) -> Result<LegReply, HandlerError> {
+        let (mode, trip) = match leg.mode {
+            LegMode::Walk => ("walk", None),
+            LegMode::Ride(trip) => ("ride", trip),
+        };
+        Ok(LegReply {
+            mode,
+            trip,
+            from: self.to_stop(leg.from)?,
+            to: self.to_stop(leg.to)?,
+            departure: Self::to_millis(leg.departure, timezone),
+            arrival: Self::to_millis(leg.arrival, timezone),
+            stops: leg
+                .stops
+                .iter()
+                .map(|idx| self.to_stop(*idx))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn handle_route(&self, body: &RouteBody) -> Result<RouteReply, HandlerError> {
+        let duration = self.parse_minutes(body.minutes)?;
+        let timezone = self.parse_timezone(&body.routing)?;
+        let start = Self::parse_start(body.start, timezone)?;
+        let station = self.parse_station(&body.station)?;
+        let mut algo = self.algo(&body.routing)?;
+        let walking = algo.options().walking;
+        let (seeds, _) =
+            self.parse_seeds(body.id.as_deref(), body.lat, body.lon, duration, &walking)?;
+        let route = algo
+            .connection(&seeds, station, start, duration, None)
+            .map_err(|_| HandlerError::InternalServerError("failed dijsktra".to_owned()))?
+            .ok_or(HandlerError::BadRequest("station not reachable".to_owned()))?
+            .route;
+        Ok(RouteReply {
+            offset: route.offset.num_minutes(),
+            legs: route
+                .legs
+                .iter()
+                .map(|leg| self.to_leg(leg, timezone))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
 
-        Ok(IsochroneReply {
-            area: merged.chamberlain_duquette_unsigned_area() / 1_000_000.0,
-            diameter: cascade::diameter(&merged) / 1000.0,
-            geometry: geojson::GeoJson::from(&merged),
+impl IsochroneHandler {
+    fn handle_matrix(&self, body: &MatrixBody) -> Result<MatrixReply, HandlerError> {
+        if body.origins.is_empty() || body.destinations.is_empty() {
+            return Err(HandlerError::BadRequest("no stations given".to_owned()));
+        }
+        if body.origins.len() > MAX_MATRIX_ORIGINS
+            || body.origins.len() * body.destinations.len() > MAX_MATRIX_CELLS
+        {
+            return Err(HandlerError::BadRequest("matrix too large".to_owned()));
+        }
+        if body.routing.arrive_by {
+            return Err(HandlerError::BadRequest(
+                "arrive by is not supported for matrices".to_owned(),
+            ));
+        }
+        let duration = self.parse_minutes(body.minutes)?;
+        let timezone = self.parse_timezone(&body.routing)?;
+        let start = Self::parse_start(body.start, timezone)?;
+        let parse = |ids: &[String]| {
+            ids.iter()
+                .map(|id| self.parse_station(id))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let origins = parse(&body.origins)?;
+        let destinations = parse(&body.destinations)?;
+        // rows run one after another on this thread, so the request stays within its permit
+        let mut algo = self.algo(&body.routing)?;
+        let matrix = Matrix::compute_with(&mut algo, &origins, &destinations, start, duration)
+            .map_err(|_| HandlerError::InternalServerError("failed dijsktra".to_owned()))?;
+        let rows = |cell: &dyn Fn(usize, usize) -> Option<i64>| {
+            (0..origins.len())
+                .map(|row| (0..destinations.len()).map(|col| cell(row, col)).collect())
+                .collect()
+        };
+        Ok(MatrixReply {
+            minutes: rows(&|row, col| matrix.travel_time(row, col).map(|t| t.num_minutes())),
+            arrivals: rows(&|row, col| {
+                matrix
+                    .arrival(row, col)
+                    .map(|arrival| Self::to_millis(arrival, timezone))
+            }),
         })
     }
 }
 
+impl From<Isochrone> for IsochroneReply {
+    fn from(isochrone: Isochrone) -> Self {
+        IsochroneReply {
+            area: isochrone.area,
+            diameter: isochrone.diameter,
+            geometry: geojson::GeoJson::from(&isochrone.polygon),
+        }
+    }
+}
+
+fn to_status<T: Serialize>(result: Result<T, HandlerError>) -> reply::WithStatus<reply::Json> {
+    match result {
+        Ok(reply) => reply::with_status(reply::json(&reply), StatusCode::OK),
+        Err(HandlerError::BadRequest(msg)) => {
+            reply::with_status(reply::json(&msg), StatusCode::BAD_REQUEST)
+        }
+        Err(HandlerError::InternalServerError(msg)) => {
+            reply::with_status(reply::json(&msg), StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Creates a POST endpoint, which limits concurrent computations using the semaphore.
+fn endpoint<B, R>(
+    path: impl Filter<Extract = (), Error = warp::Rejection> + Clone + Send + Sync + 'static,
+    handler: Arc<IsochroneHandler>,
+    semaphore: Arc<Semaphore>,
+    handle: fn(&IsochroneHandler, &B) -> Result<R, HandlerError>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+where
+    B: DeserializeOwned + Send + 'static,
+    R: Serialize,
+{
+    warp::post()
+        .and(path)
+        .and(warp::body::json::<B>())
+        .then(move |body: B| {
+            let local_handler = handler.clone();
+            let local_semaphore = semaphore.clone();
+            async move {
+                let _permit = local_semaphore
+                    .acquire()
+                    .await
+                    .expect("semaphore closed unexpectedly");
+                to_status(handle(&local_handler, &body))
+            }
+        })
+}
+
 #[tokio::main]
 async fn main() {
     let max_minutes = match std::env::var("FASTREACH_MAX_MINUTES") {
@@ -101,32 +697,50 @@ async fn main() {
     let static_path =
         std::env::var("FASTREACH_STATIC").unwrap_or_else(|_| STATIC_DEFAULT.to_owned());
 
-    let graph = Graph::from_slice(&GRAPH_DATA).expect("failed to parse graph");
+    let graph = &*GRAPH;
     let node_count = graph.nodes.len();
-    let semaphore = Arc::new(tokio::sync::Semaphore::new(parallel));
-    let iso_handler = Arc::new(IsochroneHandler { graph, max_minutes });
-    let api = warp::post()
-        .and(warp::path!("api" / "v1" / "isochrone"))
-        .and(warp::body::json::<IsochroneBody>())
-        .then(move |body: IsochroneBody| {
-            let local_handler = iso_handler.clone();
-            let local_semaphore = semaphore.clone();
-            async move {
-                let _permit = local_semaphore
-                    .acquire()
-                    .await
-                    .expect("semaphore closed unexpectedly");
-                match local_handler.handle_isochrone(&body) {
-                    Ok(reply) => reply::with_status(reply::json(&reply), StatusCode::OK),
-                    Err(HandlerError::BadRequest(msg)) => {
-                        reply::with_status(reply::json(&msg), StatusCode::BAD_REQUEST)
-                    }
-                    Err(HandlerError::InternalServerError(msg)) => {
-                        reply::with_status(reply::json(&msg), StatusCode::INTERNAL_SERVER_ERROR)
-                    }
-                }
-            }
-        });
+    let semaphore = Arc::new(Semaphore::new(parallel));
+    let iso_handler = Arc::new(IsochroneHandler {
+        graph,
+        tree: NodeTree::new(graph),
+        max_minutes,
+    });
+    let api = endpoint(
+        warp::path!("api" / "v1" / "isochrone"),
+        iso_handler.clone(),
+        semaphore.clone(),
+        IsochroneHandler::handle_isochrone,
+    )
+    .or(endpoint(
+        warp::path!("api" / "v1" / "isochrone" / "multi"),
+        iso_handler.clone(),
+        semaphore.clone(),
+        IsochroneHandler::handle_multi_isochrone,
+    ))
+    .or(endpoint(
+        warp::path!("api" / "v1" / "isochrone" / "bands"),
+        iso_handler.clone(),
+        semaphore.clone(),
+        IsochroneHandler::handle_bands,
+    ))
+    .or(endpoint(
+        warp::path!("api" / "v1" / "isochrone" / "profile"),
+        iso_handler.clone(),
+        semaphore.clone(),
+        IsochroneHandler::handle_profile,
+    ))
+    .or(endpoint(
+        warp::path!("api" / "v1" / "route"),
+        iso_handler.clone(),
+        semaphore.clone(),
+        IsochroneHandler::handle_route,
+    ))
+    .or(endpoint(
+        warp::path!("api" / "v1" / "matrix"),
+        iso_handler,
+        semaphore,
+        IsochroneHandler::handle_matrix,
+    ));
 
     let serve = warp::serve(api.or(filters::static_content(static_path)))
         .bind(([0, 0, 0, 0], 8080))
@@ -143,3 +757,84 @@ async fn main() {
 
     println!("Bye");
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+    use chrono_tz::{Europe::Berlin, Tz};
+    use fastreach_core::header::Metadata;
+
+    use super::{HandlerError, IsochroneHandler};
+
+    fn local(day: u32, month: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn millis(utc: &str) -> i64 {
+        utc.parse::<DateTime<Utc>>().unwrap().timestamp_millis()
+    }
+
+    #[test]
+    fn start_in_timetable_zone() {
+        let start = IsochroneHandler::parse_start(millis("2024-01-08T09:00:00Z"), Berlin);
+        assert_eq!(start.unwrap(), local(8, 1, 10, 0));
+        // 01:30 UTC is 03:30 after the clocks moved forward
+        let start = IsochroneHandler::parse_start(millis("2024-03-31T01:30:00Z"), Berlin);
+        assert_eq!(start.unwrap(), local(31, 3, 3, 30));
+    }
+
+    #[test]
+    fn millis_across_dst() {
+        let to_millis = |time| IsochroneHandler::to_millis(time, Berlin);
+        assert_eq!(
+            to_millis(local(31, 3, 1, 30)),
+            millis("2024-03-31T00:30:00Z")
+        );
+        assert_eq!(
+            to_millis(local(31, 3, 3, 30)),
+            millis("2024-03-31T01:30:00Z")
+        );
+        // 02:30 does not exist and is moved forward by the hour skipped
+        assert_eq!(
+            to_millis(local(31, 3, 2, 30)),
+            millis("2024-03-31T01:30:00Z")
+        );
+        // 02:30 exists twice when the clocks move back, the earlier one is used
+        assert_eq!(
+            to_millis(local(27, 10, 2, 30)),
+            millis("2024-10-27T00:30:00Z")
+        );
+    }
+
+    #[test]
+    fn requested_time_zone() {
+        let zone = |metadata, requested| IsochroneHandler::timetable_zone(metadata, requested);
+        assert_eq!(zone(None, None).unwrap(), Tz::UTC);
+        assert_eq!(zone(None, Some("Europe/Berlin")).unwrap(), Berlin);
+        assert!(matches!(
+            zone(None, Some("Mars/Olympus")),
+            Err(HandlerError::BadRequest(_))
+        ));
+        let metadata = Metadata {
+            version: 1,
+            flags: 0,
+            created: DateTime::from_timestamp(0, 0).unwrap(),
+            valid_from: local(1, 1, 0, 0).date(),
+            valid_to: local(31, 12, 0, 0).date(),
+            timezone: Berlin,
+            source: String::new(),
+        };
+        assert_eq!(zone(Some(&metadata), None).unwrap(), Berlin);
+        assert_eq!(
+            zone(Some(&metadata), Some("Europe/Berlin")).unwrap(),
+            Berlin
+        );
+        assert!(matches!(
+            zone(Some(&metadata), Some("UTC")),
+            Err(HandlerError::BadRequest(_))
+        ));
+    }
+}
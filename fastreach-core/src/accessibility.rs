@@ -0,0 +1,49 @@
+use chrono::{Duration, NaiveDateTime};
+use rayon::iter::ParallelIterator;
+
+use crate::{
+    batch::{self, Query},
+    graph::{Error, Graph, SearchOptions},
+};
+
+/// Size of the area reachable from a single station.
+pub struct Accessibility {
+    pub node: usize,
+    /// number of stations reached within the duration including the start
+    pub reached: usize,
+    /// in km2
+    pub area: f32,
+    /// in km
+    pub diameter: f32,
+}
+
+/// Computes the isochrone of every node departing at `start` in parallel.
+/// Returns the nodes ordered by descending area, so strong nodes come first.
+/// # Errors
+/// If underlying data is invalid.
+pub fn rank(
+    graph: &Graph<'_>,
+    options: SearchOptions,
+    start: NaiveDateTime,
+    duration: Duration,
+) -> Result<Vec<Accessibility>, Error> {
+    let queries: Vec<Query> = (0..graph.nodes.len())
+        .map(|node| Query {
+            node,
+            start,
+            duration,
+        })
+        .collect();
+    let mut ranking = batch::isochrones(graph, options, &queries)
+        .map(|outcome| {
+            outcome.map(|outcome| Accessibility {
+                node: outcome.query.node,
+                reached: outcome.reached,
+                area: outcome.isochrone.area,
+                diameter: outcome.isochrone.diameter,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    ranking.sort_by(|a, b| b.area.total_cmp(&a.area).then(a.node.cmp(&b.node)));
+    Ok(ranking)
+}
@@ -0,0 +1,49 @@
+use chrono::{Duration, NaiveDateTime};
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    cascade::Isochrone,
+    graph::{Error, Graph, IsochroneDijsktra, SearchOptions, Seed, TimedNode},
+    workspace::Workspace,
+};
+
+/// Isochrone request of a batch.
+#[derive(Clone, Copy, Debug)]
+pub struct Query {
+    pub node: usize,
+    /// departure in the time zone of the graph
+    pub start: NaiveDateTime,
+    pub duration: Duration,
+}
+
+/// Result of a single query.
+pub struct Outcome {
+    pub query: Query,
+    /// number of stations reached within the duration including the start
+    pub reached: usize,
+    pub isochrone: Isochrone,
+}
+
+/// Runs the queries on the rayon thread pool, where each worker thread reuses the memory
+/// of its previous search.
+/// Nothing is computed until the returned iterator is consumed, which either collects
+/// the outcomes in the order of the queries or handles each as soon as it is done.
+#[must_use]
+pub fn isochrones<'a, 'b: 'a>(
+    graph: &'a Graph<'b>,
+    options: SearchOptions,
+    queries: &'a [Query],
+) -> impl IndexedParallelIterator<Item = Result<Outcome, Error>> + use<'a, 'b> {
+    queries.par_iter().map(move |query| {
+        let algo = IsochroneDijsktra::new(graph).with_options(options);
+        Workspace::pooled(algo, |algo| {
+            let reached = algo.search(&[Seed::new(query.node)], query.start, query.duration)?;
+            let nodes: Vec<TimedNode<'_, '_>> = reached.nodes().collect();
+            Ok(Outcome {
+                query: *query,
+                reached: reached.durations().count(),
+                isochrone: Isochrone::from_nodes(&nodes),
+            })
+        })
+    })
+}
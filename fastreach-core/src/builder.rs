@@ -0,0 +1,619 @@
+use std::{collections::hash_map::Entry, io::Write};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use fnv::FnvHashMap;
+use geo::{Distance, Haversine};
+use rstar::{primitives::GeomWithData, RTree};
+
+use crate::{
+    error::BuildError,
+    graph::{
+        decode_date, encode_date, envelope_around, Graph, Journey, Walking, SECTION_CHANGE_TIMES,
+        SECTION_TRIPS,
+    },
+    header::{Metadata, FLAG_CHANGE_TIMES, FLAG_TRIPS, FORMAT_VERSION},
+};
+
+/// Identifies an operating period added to a `GraphBuilder`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PeriodId(usize);
+
+struct NodeData {
+    id: u64,
+    lat: f32,
+    lon: f32,
+    name: String,
+}
+
+struct PeriodData {
+    start: NaiveDate,
+    end: NaiveDate,
+    /// one bit per day starting with start
+    valid_days: Vec<u8>,
+}
+
+struct JourneyData {
+    departure: u16,
+    arrival: u16,
+    period: PeriodId,
+    trip: Option<u32>,
+}
+
+struct EdgeData {
+    start: u32,
+    end: u32,
+    walk: Option<u16>,
+    journeys: Vec<JourneyData>,
+}
+
+/// Collects nodes, edges, journeys and operating periods and writes them
+/// in the format read by `Graph::from_slice`.
+pub struct GraphBuilder {
+    nodes: Vec<NodeData>,
+    ids: FnvHashMap<u64, u32>,
+    edges: Vec<EdgeData>,
+    /// index of the edge between two nodes
+    edge_ids: FnvHashMap<(u32, u32), usize>,
+    periods: Vec<PeriodData>,
+    change_times: Vec<(u32, u16)>,
+    created: DateTime<Utc>,
+    timezone: chrono_tz::Tz,
+    source: String,
+}
+
+impl Default for GraphBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GraphBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            ids: FnvHashMap::default(),
+            edges: Vec::new(),
+            edge_ids: FnvHashMap::default(),
+            periods: Vec::new(),
+            change_times: Vec::new(),
+            created: Utc::now(),
+            timezone: chrono_tz::UTC,
+            source: String::new(),
+        }
+    }
+
+    /// Collects the data of a loaded graph, for example to add footpaths to an existing file.
+    /// # Errors
+    /// If a name is not UTF-8 or a date cannot be decoded.
+    pub fn from_graph(graph: &Graph<'_>) -> Result<Self, BuildError> {
+        let mut builder = Self::new();
+        if let Some(metadata) = graph.metadata() {
+            builder = builder
+                .with_timezone(metadata.timezone)
+                .with_source(&metadata.source);
+        }
+        for node in &graph.nodes {
+            let name = node
+                .name()
+                .map_err(|_| BuildError::Invalid { field: "name" })?;
+            builder.add_node(node.id(), node.lat(), node.lon(), name)?;
+        }
+        // edges store their own copy of shared operating periods
+        let mut periods = FnvHashMap::<(u16, u16, Vec<u8>), PeriodId>::default();
+        for edge in graph.nodes.iter().flat_map(|node| &node.outgoing) {
+            if edge.walk() != u16::MAX {
+                let walk = chrono::Duration::seconds(edge.walk().into());
+                builder.add_walk(edge.start(), edge.end(), walk)?;
+            }
+            let mut local = Vec::new();
+            for period in edge.operating_periods() {
+                let key = (period.start(), period.end(), period.valid_days().to_vec());
+                let id = match periods.entry(key) {
+                    Entry::Occupied(entry) => *entry.get(),
+                    Entry::Vacant(entry) => {
+                        let (start, end, valid_days) = entry.key();
+                        let (Some(start), Some(end)) = (decode_date(*start), decode_date(*end))
+                        else {
+                            return Err(BuildError::Invalid {
+                                field: "operating period",
+                            });
+                        };
+                        builder.periods.push(PeriodData {
+                            start,
+                            end,
+                            valid_days: valid_days.clone(),
+                        });
+                        *entry.insert(PeriodId(builder.periods.len() - 1))
+                    }
+                };
+                local.push(id);
+            }
+            for journey in edge.journeys() {
+                let period = local[usize::from(journey.operating_period_index())];
+                // legacy graphs wrap arrivals after midnight
+                let mut arrival = journey.arrival();
+                while arrival < journey.departure() {
+                    arrival += 24 * 60;
+                }
+                builder.add_journey(
+                    edge.start(),
+                    edge.end(),
+                    journey.departure(),
+                    arrival,
+                    period,
+                    journey.trip(),
+                )?;
+            }
+        }
+        for (node, change) in &graph.change_times {
+            builder.set_change_time(*node, *change)?;
+        }
+        Ok(builder)
+    }
+
+    /// Sets the time zone of the timetable.
+    #[must_use]
+    pub fn with_timezone(mut self, timezone: chrono_tz::Tz) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Sets the description of the data the graph is generated from.
+    #[must_use]
+    pub fn with_source(mut self, source: &str) -> Self {
+        source.clone_into(&mut self.source);
+        self
+    }
+
+    /// Sets the creation time, which defaults to now.
+    #[must_use]
+    pub fn with_created(mut self, created: DateTime<Utc>) -> Self {
+        self.created = created;
+        self
+    }
+
+    /// Index of the node with the given id.
+    #[must_use]
+    pub fn node_index(&self, id: u64) -> Option<u32> {
+        self.ids.get(&id).copied()
+    }
+
+    /// Location of the node with the given index.
+    #[must_use]
+    pub fn point(&self, node: u32) -> Option<geo::Point<f32>> {
+        self.nodes
+            .get(node as usize)
+            .map(|node| geo::Point::new(node.lon, node.lat))
+    }
+
+    /// Adds a node and returns its index.
+    /// # Errors
+    /// If the id is already taken, the coordinates are out of range or the name is too long.
+    pub fn add_node(&mut self, id: u64, lat: f32, lon: f32, name: &str) -> Result<u32, BuildError> {
+        if self.ids.contains_key(&id) {
+            return Err(BuildError::DuplicateNode { id });
+        }
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+            return Err(BuildError::Invalid {
+                field: "coordinates",
+            });
+        }
+        if u16::try_from(name.len()).is_err() {
+            return Err(BuildError::TooLarge { what: "name" });
+        }
+        let idx = u32::try_from(self.nodes.len())
+            .map_err(|_| BuildError::TooLarge { what: "node count" })?;
+        self.nodes.push(NodeData {
+            id,
+            lat,
+            lon,
+            name: name.to_owned(),
+        });
+        self.ids.insert(id, idx);
+        Ok(idx)
+    }
+
+    /// Adds an operating period from start to end including both,
+    /// which is valid on the given days.
+    /// # Errors
+    /// If the dates cannot be encoded, end is before start or a day is outside the period.
+    pub fn add_period(
+        &mut self,
+        start: NaiveDate,
+        end: NaiveDate,
+        valid_days: impl IntoIterator<Item = NaiveDate>,
+    ) -> Result<PeriodId, BuildError> {
+        if encode_date(start).is_none() || encode_date(end).is_none() || end < start {
+            return Err(BuildError::Invalid {
+                field: "operating period",
+            });
+        }
+        let days = usize::try_from((end - start).num_days()).unwrap_or_default() + 1;
+        let mut bits = vec![0; days.div_ceil(8)];
+        if u8::try_from(bits.len()).is_err() {
+            return Err(BuildError::TooLarge {
+                what: "operating period",
+            });
+        }
+        for day in valid_days {
+            let offset = usize::try_from((day - start).num_days())
+                .ok()
+                .filter(|offset| *offset < days)
+                .ok_or(BuildError::Invalid { field: "valid day" })?;
+            bits[offset / 8] |= 1 << (offset % 8);
+        }
+        self.periods.push(PeriodData {
+            start,
+            end,
+            valid_days: bits,
+        });
+        Ok(PeriodId(self.periods.len() - 1))
+    }
+
+    fn edge(&mut self, start: u32, end: u32) -> Result<&mut EdgeData, BuildError> {
+        for node in [start, end] {
+            if node as usize >= self.nodes.len() {
+                return Err(BuildError::MissingNode { node });
+            }
+        }
+        let idx = *self.edge_ids.entry((start, end)).or_insert_with(|| {
+            self.edges.push(EdgeData {
+                start,
+                end,
+                walk: None,
+                journeys: Vec::new(),
+            });
+            self.edges.len() - 1
+        });
+        Ok(&mut self.edges[idx])
+    }
+
+    /// Allows walking from start to end within the duration.
+    /// A shorter walk replaces a longer one.
+    /// # Errors
+    /// If a node is missing or the walk is negative or too long.
+    pub fn add_walk(
+        &mut self,
+        start: u32,
+        end: u32,
+        duration: chrono::Duration,
+    ) -> Result<(), BuildError> {
+        let seconds = u16::try_from(duration.num_seconds())
+            .ok()
+            .filter(|s| *s < u16::MAX)
+            .ok_or(BuildError::Invalid { field: "walk" })?;
+        let edge = self.edge(start, end)?;
+        edge.walk = Some(edge.walk.map_or(seconds, |walk| walk.min(seconds)));
+        Ok(())
+    }
+
+    /// Adds a journey from start to end operating on the days of the period.
+    /// Times are minutes after midnight of the service day, journeys arriving
+    /// after midnight use times beyond 24:00.
+    /// # Errors
+    /// If a node or the period is missing, the times are out of range
+    /// or the arrival is before the departure.
+    pub fn add_journey(
+        &mut self,
+        start: u32,
+        end: u32,
+        departure: u16,
+        arrival: u16,
+        period: PeriodId,
+        trip: Option<u32>,
+    ) -> Result<(), BuildError> {
+        if arrival > Journey::MAX_MINUTES || arrival < departure {
+            return Err(BuildError::Invalid {
+                field: "journey times",
+            });
+        }
+        if period.0 >= self.periods.len() {
+            return Err(BuildError::Invalid {
+                field: "operating period",
+            });
+        }
+        self.edge(start, end)?.journeys.push(JourneyData {
+            departure,
+            arrival,
+            period,
+            trip,
+        });
+        Ok(())
+    }
+
+    /// Allows walking between all nodes within the radius in m, which is useful for
+    /// timetables without transfers. A walk takes as long as the direct distance times
+    /// the detour factor at the default walking speed. Existing walks, like transfers of
+    /// the timetable, are kept. Returns the number of added walks.
+    /// # Errors
+    /// If the radius or detour is invalid or a walk becomes too long.
+    pub fn add_footpaths(&mut self, radius: f32, detour: f32) -> Result<usize, BuildError> {
+        if !radius.is_finite() || radius < 0.0 {
+            return Err(BuildError::Invalid {
+                field: "footpath radius",
+            });
+        }
+        if !detour.is_finite() || detour < 1.0 {
+            return Err(BuildError::Invalid { field: "detour" });
+        }
+        let walking = Walking::default();
+        let points: Vec<_> = (0..self.nodes.len())
+            .filter_map(|idx| {
+                let idx = u32::try_from(idx).ok()?;
+                Some((idx, self.point(idx)?))
+            })
+            .collect();
+        let tree = RTree::bulk_load(
+            points
+                .iter()
+                .map(|(idx, point)| GeomWithData::new([point.x(), point.y()], *idx))
+                .collect(),
+        );
+        let mut count = 0;
+        for (start, point) in points {
+            for entry in tree.locate_in_envelope_intersecting(&envelope_around(point, radius)) {
+                let end = entry.data;
+                let distance = Haversine.distance(point, geo::Point::from(*entry.geom()));
+                if end == start || distance > radius || self.has_walk(start, end) {
+                    continue;
+                }
+                self.add_walk(start, end, walking.duration(distance * detour))?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    fn has_walk(&self, start: u32, end: u32) -> bool {
+        self.edge_ids
+            .get(&(start, end))
+            .is_some_and(|idx| self.edges[*idx].walk.is_some())
+    }
+
+    /// Sets the minimum time to change between vehicles at the node.
+    /// # Errors
+    /// If the node is missing or the duration is negative or too long.
+    pub fn set_change_time(
+        &mut self,
+        node: u32,
+        duration: chrono::Duration,
+    ) -> Result<(), BuildError> {
+        if node as usize >= self.nodes.len() {
+            return Err(BuildError::MissingNode { node });
+        }
+        let seconds = u16::try_from(duration.num_seconds()).map_err(|_| BuildError::Invalid {
+            field: "change time",
+        })?;
+        self.change_times.retain(|(n, _)| *n != node);
+        self.change_times.push((node, seconds));
+        Ok(())
+    }
+
+    /// Header describing the graph as it would be written.
+    /// The validity spans all operating periods used by journeys.
+    /// # Errors
+    /// If journeys with and without trips are mixed.
+    pub fn metadata(&self) -> Result<Metadata, BuildError> {
+        let mut flags = 0;
+        if self.has_trips()? {
+            flags |= FLAG_TRIPS;
+        }
+        if !self.change_times.is_empty() {
+            flags |= FLAG_CHANGE_TIMES;
+        }
+        let used = self
+            .edges
+            .iter()
+            .flat_map(|e| &e.journeys)
+            .map(|j| &self.periods[j.period.0]);
+        let today = self.created.date_naive();
+        let valid_from = used.clone().map(|p| p.start).min().unwrap_or(today);
+        let valid_to = used.map(|p| p.end).max().unwrap_or(today);
+        Ok(Metadata {
+            version: FORMAT_VERSION,
+            flags,
+            created: self.created,
+            valid_from,
+            valid_to,
+            timezone: self.timezone,
+            source: self.source.clone(),
+        })
+    }
+
+    fn has_trips(&self) -> Result<bool, BuildError> {
+        let mut journeys = self.edges.iter().flat_map(|e| &e.journeys);
+        let Some(first) = journeys.next() else {
+            return Ok(false);
+        };
+        let has_trips = first.trip.is_some();
+        if journeys.any(|j| j.trip.is_some() != has_trips) {
+            return Err(BuildError::IncompleteTrips);
+        }
+        Ok(has_trips)
+    }
+
+    /// Serializes the graph.
+    /// # Errors
+    /// If a limit of the format is exceeded or writing fails.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), BuildError> {
+        let metadata = self.metadata()?;
+        metadata.write(&mut writer)?;
+        let node_count = u32::try_from(self.nodes.len())
+            .map_err(|_| BuildError::TooLarge { what: "node count" })?;
+        writer.write_all(&node_count.to_le_bytes())?;
+        for node in &self.nodes {
+            writer.write_all(&node.id.to_le_bytes())?;
+            writer.write_all(&node.lat.to_le_bytes())?;
+            writer.write_all(&node.lon.to_le_bytes())?;
+            // checked when adding the node
+            #[allow(clippy::cast_possible_truncation)]
+            writer.write_all(&(node.name.len() as u16).to_le_bytes())?;
+            writer.write_all(node.name.as_bytes())?;
+        }
+        let edge_count = u32::try_from(self.edges.len())
+            .map_err(|_| BuildError::TooLarge { what: "edge count" })?;
+        writer.write_all(&edge_count.to_le_bytes())?;
+        for edge in &self.edges {
+            self.write_edge(edge, &mut writer)?;
+        }
+        if metadata.flags & FLAG_TRIPS != 0 {
+            let trips: Vec<u8> = self
+                .edges
+                .iter()
+                .flat_map(|e| &e.journeys)
+                .flat_map(|j| j.trip.unwrap_or_default().to_le_bytes())
+                .collect();
+            Self::write_section(SECTION_TRIPS, &trips, &mut writer)?;
+        }
+        if metadata.flags & FLAG_CHANGE_TIMES != 0 {
+            // ordered by node, as loaded graphs do not keep the order of the section
+            let mut entries = self.change_times.clone();
+            entries.sort_unstable();
+            let mut change_times = Vec::with_capacity(entries.len() * 6);
+            for (node, seconds) in &entries {
+                change_times.extend(node.to_le_bytes());
+                change_times.extend(seconds.to_le_bytes());
+            }
+            Self::write_section(SECTION_CHANGE_TIMES, &change_times, &mut writer)?;
+        }
+        Ok(())
+    }
+
+    /// Serializes the graph into a buffer.
+    /// # Errors
+    /// If a limit of the format is exceeded.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BuildError> {
+        let mut data = Vec::new();
+        self.write(&mut data)?;
+        Ok(data)
+    }
+
+    /// Writes an edge with the operating periods used by its journeys.
+    fn write_edge<W: Write>(&self, edge: &EdgeData, writer: &mut W) -> Result<(), BuildError> {
+        let journey_count = u16::try_from(edge.journeys.len())
+            .map_err(|_| BuildError::TooLarge { what: "journeys" })?;
+        // periods are stored per edge, so journeys reference the local index
+        let mut local = FnvHashMap::<PeriodId, u16>::default();
+        let mut periods = Vec::new();
+        let mut journeys = Vec::with_capacity(edge.journeys.len() * 5);
+        for journey in &edge.journeys {
+            let next =
+                u16::try_from(local.len()).map_err(|_| BuildError::TooLarge { what: "periods" })?;
+            let index = *local.entry(journey.period).or_insert_with(|| {
+                let period = &self.periods[journey.period.0];
+                // dates are checked when adding the period
+                let start = encode_date(period.start).unwrap_or_default();
+                let end = encode_date(period.end).unwrap_or_default();
+                periods.extend(start.to_le_bytes());
+                periods.extend(end.to_le_bytes());
+                #[allow(clippy::cast_possible_truncation)]
+                periods.push(period.valid_days.len() as u8);
+                periods.extend(&period.valid_days);
+                next
+            });
+            let (departure, arrival) = (journey.departure, journey.arrival);
+            #[allow(clippy::cast_possible_truncation)]
+            journeys.extend([
+                (arrival & 0xFF) as u8,
+                ((arrival >> 8) << 4 | departure >> 8) as u8,
+                (departure & 0xFF) as u8,
+            ]);
+            journeys.extend(index.to_le_bytes());
+        }
+        let period_bytes =
+            u16::try_from(periods.len()).map_err(|_| BuildError::TooLarge { what: "periods" })?;
+        writer.write_all(&edge.start.to_le_bytes())?;
+        writer.write_all(&edge.end.to_le_bytes())?;
+        writer.write_all(&edge.walk.unwrap_or(u16::MAX).to_le_bytes())?;
+        writer.write_all(&journey_count.to_le_bytes())?;
+        writer.write_all(&journeys)?;
+        writer.write_all(&period_bytes.to_le_bytes())?;
+        writer.write_all(&periods)?;
+        Ok(())
+    }
+
+    fn write_section<W: Write>(tag: u8, data: &[u8], writer: &mut W) -> Result<(), BuildError> {
+        let len =
+            u32::try_from(data.len()).map_err(|_| BuildError::TooLarge { what: "section" })?;
+        writer.write_all(&[tag])?;
+        writer.write_all(&len.to_le_bytes())?;
+        writer.write_all(data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration};
+
+    use super::GraphBuilder;
+    use crate::{
+        graph::Graph,
+        testing::{date, Layout},
+    };
+
+    #[test]
+    fn round_trip_is_identical() {
+        let created = DateTime::from_timestamp(1_704_067_200, 0).unwrap();
+        let mut builder = GraphBuilder::new()
+            .with_timezone(chrono_tz::Europe::Berlin)
+            .with_source("test")
+            .with_created(created);
+        let a = builder.add_node(1, 50.0, 8.0, "Alpha").unwrap();
+        let b = builder.add_node(2, 50.1, 8.0, "Beta").unwrap();
+        let c = builder.add_node(3, 50.2, 8.1, "Gamma").unwrap();
+        let weekdays = builder
+            .add_period(date(1), date(31), (1..=5).map(date))
+            .unwrap();
+        let sundays = builder
+            .add_period(date(7), date(28), [7, 14, 21, 28].map(date))
+            .unwrap();
+        // edges are added in the order of their start, which is the order of a loaded graph
+        builder
+            .add_journey(a, b, 480, 495, weekdays, Some(1))
+            .unwrap();
+        builder
+            .add_journey(a, b, 1430, 1460, sundays, Some(2))
+            .unwrap();
+        builder.add_walk(a, c, Duration::minutes(12)).unwrap();
+        builder
+            .add_journey(b, c, 1510, 1525, sundays, Some(2))
+            .unwrap();
+        builder.set_change_time(b, Duration::minutes(4)).unwrap();
+        builder.set_change_time(a, Duration::minutes(2)).unwrap();
+        let data = builder.to_bytes().unwrap();
+
+        let graph = Graph::from_slice(&data).unwrap();
+        let copy = GraphBuilder::from_graph(&graph)
+            .unwrap()
+            .with_created(created);
+        assert_eq!(copy.to_bytes().unwrap(), data);
+    }
+
+    #[test]
+    fn legacy_arrivals_are_unwrapped() {
+        let mut builder = GraphBuilder::new();
+        let a = builder.add_node(1, 50.0, 8.0, "A").unwrap();
+        let b = builder.add_node(2, 51.0, 8.0, "B").unwrap();
+        let period = builder.add_period(date(1), date(31), [date(8)]).unwrap();
+        builder.add_journey(a, b, 1430, 1460, period, None).unwrap();
+        let data = builder.to_bytes().unwrap();
+        let mut legacy = data[Layout::of(&data).body..].to_vec();
+        let journey = Layout::of(&legacy).edges[0].journeys[0];
+        // wrapped arrival at 00:20
+        legacy[journey..journey + 2].copy_from_slice(&[20, 0x05]);
+
+        let graph = Graph::from_legacy_slice(&legacy).unwrap();
+        let wrapped = graph.nodes[0].outgoing[0].journeys().next().unwrap();
+        assert_eq!((wrapped.departure(), wrapped.arrival()), (1430, 20));
+        let copy = GraphBuilder::from_graph(&graph)
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        let graph = Graph::from_slice(&copy).unwrap();
+        let journey = graph.nodes[0].outgoing[0].journeys().next().unwrap();
+        assert_eq!((journey.departure(), journey.arrival()), (1430, 1460));
+        assert_eq!(journey.times_on(date(8)), wrapped.times_on(date(8)));
+    }
+}
@@ -1,6 +1,6 @@
 use geo::{
-    BoundingRect, ConvexHull, CoordsIter, Distance, GeoFloat, Haversine, Intersects, MultiPolygon,
-    Polygon,
+    BoundingRect, ChamberlainDuquetteArea, ConvexHull, CoordsIter, Distance, GeoFloat, Haversine,
+    Intersects, MultiPolygon, Polygon,
 };
 use geo_clipper::Clipper;
 use num_traits::FromPrimitive;
@@ -10,6 +10,39 @@ use crate::graph::TimedNode;
 
 const SCALE_FACTOR: f32 = 2000.0;
 
+/// Merged reachable area.
+pub struct Isochrone {
+    pub polygon: MultiPolygon<f32>,
+    /// in km2
+    pub area: f32,
+    /// in km
+    pub diameter: f32,
+}
+
+impl Isochrone {
+    /// Merges the given polygons and measures the result.
+    /// Without polygons the isochrone is empty and has no area.
+    /// # Panics
+    /// If any polygon is empty.
+    #[must_use]
+    pub fn from_polys(polys: Vec<Polygon<f32>>) -> Self {
+        let polygon = union_polys(polys);
+        Self {
+            area: polygon.chamberlain_duquette_unsigned_area() / 1_000_000.0,
+            diameter: diameter(&polygon) / 1000.0,
+            polygon,
+        }
+    }
+
+    /// Merges the circles around the given nodes and measures the result.
+    /// # Panics
+    /// If any `TimedNode` produces an empty polygon.
+    #[must_use]
+    pub fn from_nodes(nodes: &[TimedNode<'_, '_>]) -> Self {
+        Self::from_polys(nodes.iter().map(TimedNode::to_poly).collect())
+    }
+}
+
 /// Unifies the given polygons.
 /// # Panics
 /// If any polygon is empty.
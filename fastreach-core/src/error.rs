@@ -0,0 +1,135 @@
+use thiserror::Error;
+
+/// Reasons for rejecting a graph file. Offsets are bytes from the start of the file.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    #[error("unexpected end of data at byte {offset}")]
+    Truncated { offset: usize },
+    #[error("missing magic bytes")]
+    MissingMagic,
+    #[error("unsupported format version {version}")]
+    UnsupportedVersion { version: u16 },
+    #[error("unsupported flags {flags:#x}")]
+    UnsupportedFlags { flags: u16 },
+    #[error("invalid {field} at byte {offset}")]
+    Invalid { offset: usize, field: &'static str },
+    #[error("record at byte {offset} references missing node {node}")]
+    MissingNode { offset: usize, node: u32 },
+    #[error("journey at byte {offset} references missing operating period {index}")]
+    MissingPeriod { offset: usize, index: u16 },
+    #[error(
+        "operating period at byte {offset} has {actual} valid day bytes instead of {expected}"
+    )]
+    ValidDays {
+        offset: usize,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("section {tag} at byte {offset} has invalid length {len}")]
+    SectionLength { offset: usize, tag: u8, len: usize },
+    #[error("sections for flags {flags:#x} are missing")]
+    MissingSection { flags: u16 },
+}
+
+/// Reasons for rejecting data passed to the `GraphBuilder`.
+#[derive(Error, Debug)]
+pub enum BuildError {
+    #[error("duplicate node id {id}")]
+    DuplicateNode { id: u64 },
+    #[error("missing node {node}")]
+    MissingNode { node: u32 },
+    #[error("invalid {field}")]
+    Invalid { field: &'static str },
+    #[error("{what} exceeds the limits of the format")]
+    TooLarge { what: &'static str },
+    #[error("journeys with and without trip ids cannot be mixed")]
+    IncompleteTrips,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Bounds checked little endian reader, which keeps track of the offset into the file.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    /// offset of data within the file
+    base: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self::nested(data, 0)
+    }
+
+    /// Reads a part of the file starting at `base`.
+    pub fn nested(data: &'a [u8], base: usize) -> Self {
+        Self {
+            data,
+            offset: 0,
+            base,
+        }
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Offset of the next read within the file.
+    pub fn offset(&self) -> usize {
+        self.base + self.offset
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.offset
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], GraphError> {
+        let bytes = self
+            .offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.offset..end))
+            .ok_or(GraphError::Truncated {
+                offset: self.offset(),
+            })?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], GraphError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, GraphError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, GraphError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, GraphError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, GraphError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, GraphError> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    pub fn i64(&mut self) -> Result<i64, GraphError> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+
+    pub fn f32(&mut self) -> Result<f32, GraphError> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+}
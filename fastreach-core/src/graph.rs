@@ -1,22 +1,45 @@
 use std::str::Utf8Error;
 
-use byteorder::{LittleEndian as LE, ReadBytesExt};
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use chrono::{Datelike, Days, NaiveDate, NaiveDateTime, NaiveTime};
 use fnv::FnvHashMap;
 use geo::{Distance, GeoFloat, Haversine};
 use num_traits::FromPrimitive;
+use rstar::{primitives::GeomWithData, RTree};
 use smallvec::SmallVec;
 
+use crate::{
+    error::{GraphError, Reader},
+    header::{Metadata, FLAG_CHANGE_TIMES, FLAG_TRIPS},
+    route::{Connection, LabelKey, Routes, Step, Trace},
+    workspace::Workspace,
+};
+
 const MOVE_SPEED: f32 = 1000.0 / 12.0; // in m/min
+/// journeys may depart this many days after the start of their service date,
+/// as times are limited to `Journey::MAX_MINUTES`
+const SERVICE_DAY_SPAN: u64 = 2;
 
 pub struct Node<'a> {
     // binary data
     data: &'a [u8],
     /// offset in the file where edge information can be found
     pub outgoing: SmallVec<[Edge<'a>; 4]>,
+    /// edges ending at this node, used for reverse searches
+    pub incoming: SmallVec<[Edge<'a>; 4]>,
 }
 
 impl Node<'_> {
+    /// id, lat, lon and name length
+    const MIN_SIZE: usize = 18;
+
+    #[must_use]
+    pub fn id(&self) -> u64 {
+        unsafe {
+            // can only error when len of slice is not 8 which panics beforehand
+            u64::from_le_bytes(self.data[..8].try_into().unwrap_unchecked())
+        }
+    }
+
     #[must_use]
     pub fn lat(&self) -> f32 {
         unsafe {
@@ -43,6 +66,12 @@ impl Node<'_> {
     pub fn name(&self) -> Result<&str, Utf8Error> {
         std::str::from_utf8(&self.data[18..])
     }
+
+    /// Bytes of the node in the file.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
 }
 
 impl rstar::RTreeObject for Node<'_> {
@@ -53,12 +82,26 @@ impl rstar::RTreeObject for Node<'_> {
     }
 }
 
+impl rstar::RTreeObject for &Node<'_> {
+    type Envelope = rstar::AABB<[f32; 2]>;
+
+    fn envelope(&self) -> Self::Envelope {
+        (*self).envelope()
+    }
+}
+
+#[derive(Clone, Copy)]
 pub struct Edge<'a> {
     data: &'a [u8],
+    /// trip ids of the journeys, empty if the graph has no trips
+    trips: &'a [u8],
 }
 
 impl<'a> Edge<'a> {
-    const JOURNEY_SIZE: usize = 5;
+    /// Bytes of a journey in the file.
+    pub const JOURNEY_SIZE: usize = 5;
+    /// start, end, walk, journey count and period bytes
+    const MIN_SIZE: usize = 14;
 
     #[must_use]
     pub fn start(&self) -> u32 {
@@ -76,6 +119,12 @@ impl<'a> Edge<'a> {
         }
     }
 
+    /// Bytes of the edge in the file including journeys and operating periods.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
     #[must_use]
     pub fn walk(&self) -> u16 {
         unsafe {
@@ -90,9 +139,15 @@ impl<'a> Edge<'a> {
             u16::from_le_bytes(self.data[10..12].try_into().unwrap_unchecked())
         } as usize;
         let journeys = &self.data[12..12 + (journey_count * Self::JOURNEY_SIZE)];
+        let trips = self
+            .trips
+            .chunks_exact(4)
+            .map(|c| Some(u32::from_le_bytes([c[0], c[1], c[2], c[3]])))
+            .chain(std::iter::repeat(None));
         journeys
             .chunks_exact(Self::JOURNEY_SIZE)
-            .map(|c| Journey { data: c })
+            .zip(trips)
+            .map(|(c, trip)| Journey { data: c, trip })
     }
 
     #[must_use]
@@ -112,13 +167,50 @@ impl<'a> Edge<'a> {
             offset: 0,
         }
     }
+
+    /// Journeys departing on the date with their departure and arrival ordered by departure.
+    /// Includes journeys of earlier service days, which depart after midnight.
+    #[must_use]
+    pub fn departures_on(
+        &self,
+        date: NaiveDate,
+    ) -> Vec<(Journey<'a>, NaiveDateTime, NaiveDateTime)> {
+        let periods: Vec<_> = self.operating_periods().collect();
+        let day = date.and_time(NaiveTime::MIN);
+        let mut departures: Vec<_> = self
+            .journeys()
+            .flat_map(|journey| {
+                service_dates(day, day).filter_map(move |service| {
+                    let (departure, arrival) = journey.times_on(service);
+                    (departure.date() == date).then_some((journey, service, departure, arrival))
+                })
+            })
+            .filter(|(journey, service, _, _)| {
+                periods
+                    .get(usize::from(journey.operating_period_index()))
+                    .and_then(|period| period.operates_on(*service))
+                    .unwrap_or(false)
+            })
+            .map(|(journey, _, departure, arrival)| (journey, departure, arrival))
+            .collect();
+        departures.sort_by_key(|(_, departure, _)| *departure);
+        departures
+    }
 }
 
+/// Ride along an edge on the days of an operating period.
+/// Times are minutes after midnight of the service day, which may exceed 24:00
+/// for journeys operating after midnight, e.g. 25:10 is stored as 1510.
+#[derive(Clone, Copy)]
 pub struct Journey<'a> {
     data: &'a [u8],
+    trip: Option<u32>,
 }
 
 impl Journey<'_> {
+    /// Largest time, which can be stored in the 12 bits of a journey.
+    pub const MAX_MINUTES: u16 = 0xFFF;
+
     #[must_use]
     pub fn arrival(&self) -> u16 {
         u16::from(self.data[0]) | u16::from(self.data[1] & 0xF0) << 4
@@ -136,6 +228,30 @@ impl Journey<'_> {
             u16::from_le_bytes(self.data[3..5].try_into().unwrap_unchecked())
         }
     }
+
+    /// Departure and arrival when operating on the service date.
+    #[must_use]
+    pub fn times_on(&self, date: NaiveDate) -> (NaiveDateTime, NaiveDateTime) {
+        const DAY: u32 = 24 * 60;
+        let midnight = date.and_time(NaiveTime::MIN);
+        let departure = u32::from(self.departure());
+        let mut arrival = u32::from(self.arrival());
+        // graphs without times beyond 24:00 wrap arrivals after midnight,
+        // so arrivals before the departure take place on a following day
+        if arrival < departure {
+            arrival += (departure - arrival).div_ceil(DAY) * DAY;
+        }
+        (
+            midnight + chrono::Duration::minutes(departure.into()),
+            midnight + chrono::Duration::minutes(arrival.into()),
+        )
+    }
+
+    /// Identifies the vehicle run, which serves this journey. `None` if the graph has no trips.
+    #[must_use]
+    pub fn trip(&self) -> Option<u32> {
+        self.trip
+    }
 }
 
 pub struct OperatingPeriod<'a> {
@@ -159,11 +275,60 @@ impl OperatingPeriod<'_> {
         }
     }
 
+    /// Bytes of the operating period in the file.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
     #[must_use]
     pub fn valid_days(&self) -> &[u8] {
         let len = self.data[4] as usize;
         &self.data[5..5 + len]
     }
+
+    /// Whether journeys operate on the date, `None` if the dates of the period are invalid.
+    #[must_use]
+    pub fn operates_on(&self, date: NaiveDate) -> Option<bool> {
+        let start = decode_date(self.start())?;
+        let end = decode_date(self.end())?;
+        if date < start || date > end {
+            return Some(false);
+        }
+        let days = usize::try_from((date - start).num_days()).ok()?;
+        let bits = self.valid_days().get(days / 8)?;
+        Some(bits & 1 << (days % 8) != 0)
+    }
+}
+
+/// Decodes dates of operating periods, which store the year after 2000 in the lowest 7 bits,
+/// followed by 4 bits month and 5 bits day.
+#[must_use]
+pub fn decode_date(number: u16) -> Option<NaiveDate> {
+    let year = number & 0b_0000_0000_0111_1111;
+    let month = (number >> 7) & 0b_0000_0000_0000_1111;
+    let day = (number >> 11) & 0b_0000_0000_0001_1111;
+    NaiveDate::from_ymd_opt(i32::from(year) + 2000, month.into(), day.into())
+}
+
+/// Encodes a date for operating periods, `None` if the year is not within 2000 and 2127.
+#[must_use]
+pub fn encode_date(date: NaiveDate) -> Option<u16> {
+    let year = u16::try_from(date.year() - 2000)
+        .ok()
+        .filter(|y| *y < 128)?;
+    // month and day always fit into 4 and 5 bits
+    #[allow(clippy::cast_possible_truncation)]
+    Some(year | (date.month() as u16) << 7 | (date.day() as u16) << 11)
+}
+
+/// Service dates whose journeys may operate between `from` and `to`.
+fn service_dates(from: NaiveDateTime, to: NaiveDateTime) -> impl Iterator<Item = NaiveDate> {
+    let first = from
+        .date()
+        .checked_sub_days(Days::new(SERVICE_DAY_SPAN))
+        .unwrap_or(NaiveDate::MIN);
+    first.iter_days().take_while(move |date| *date <= to.date())
 }
 
 pub struct OperatingPeriodIter<'a> {
@@ -191,62 +356,259 @@ impl<'a> Iterator for OperatingPeriodIter<'a> {
 pub struct Graph<'a> {
     pub nodes: Vec<Node<'a>>,
     pub ids: FnvHashMap<u64, usize>,
+    /// per-node minimum time to change between vehicles
+    pub change_times: FnvHashMap<u32, chrono::Duration>,
+    metadata: Option<Metadata>,
+    has_trips: bool,
 }
 
-type Error = Box<dyn std::error::Error>;
+pub(crate) type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Optional sections may follow the edges. Each starts with a tag byte and its length as u32.
+/// Contains the trip id as u32 of every journey in the order of the edges.
+pub(crate) const SECTION_TRIPS: u8 = 1;
+/// Contains pairs of node index as u32 and minimum change time in seconds as u16.
+pub(crate) const SECTION_CHANGE_TIMES: u8 = 2;
 
 impl Graph<'_> {
     /// Parses the given slice into the graph.
     /// # Errors
-    /// When file is too small.
-    #[allow(clippy::cast_sign_loss, clippy::cast_lossless)]
-    pub fn from_slice(data: &[u8]) -> Result<Graph<'_>, Error> {
-        let mut reader = std::io::Cursor::new(data);
-        let node_count = reader.read_u32::<LE>()?;
-        let mut nodes = Vec::with_capacity(node_count as usize);
+    /// When the header is missing or incompatible or the file is too small.
+    pub fn from_slice(data: &[u8]) -> Result<Graph<'_>, GraphError> {
+        let mut reader = Reader::new(data);
+        let metadata = Metadata::read(&mut reader)?;
+        Self::read_body(reader, Some(metadata))
+    }
+
+    /// Parses a slice without header as written by netex-parse.
+    /// # Errors
+    /// When the data is truncated or inconsistent.
+    pub fn from_legacy_slice(data: &[u8]) -> Result<Graph<'_>, GraphError> {
+        Self::read_body(Reader::new(data), None)
+    }
+
+    /// Reads and validates every record, so that accessors cannot fail afterwards.
+    fn read_body(
+        mut reader: Reader<'_>,
+        metadata: Option<Metadata>,
+    ) -> Result<Graph<'_>, GraphError> {
+        let data = reader.data();
+        let node_count = reader.u32()?;
+        // counts of corrupt files must not cause huge allocations
+        let mut nodes =
+            Vec::with_capacity((node_count as usize).min(reader.remaining() / Node::MIN_SIZE));
         let mut ids = FnvHashMap::<u64, usize>::default();
         for i in 0..node_count {
-            let start: usize = reader.position().try_into()?;
-            let id = reader.read_u64::<LE>()?;
-            ids.insert(id, i as usize);
-            // 2 * 4 bytes lat and lon
-            reader.set_position(reader.position() + 8);
-            let name_len = reader.read_u16::<LE>()? as u64;
-            reader.set_position(reader.position() + name_len);
-            let end: usize = reader.position().try_into()?;
-            nodes.push(Node {
-                data: &data[start..end],
-                outgoing: SmallVec::new(),
-            });
+            let node = Self::read_node(&mut reader)?;
+            ids.insert(node.id(), i as usize);
+            nodes.push(node);
         }
-        let edge_count = reader.read_u32::<LE>()?;
+        let edge_count = reader.u32()?;
+        // edges are attached once the trips are known
+        let mut edges =
+            Vec::with_capacity((edge_count as usize).min(reader.remaining() / Edge::MIN_SIZE));
+        let mut journey_total = 0;
         for _ in 0..edge_count {
-            let offset: usize = reader.position().try_into()?;
-            let start = reader.read_u32::<LE>()?;
-            // end + walk_seconds 6 bytes
-            reader.set_position(reader.position() + 6);
-            let journeys_count = reader.read_u16::<LE>()? as usize;
-            reader.set_position(reader.position() + (Edge::JOURNEY_SIZE * journeys_count) as u64);
-            let periods_bytes = reader.read_u16::<LE>()?;
-            reader.set_position(reader.position() + periods_bytes as u64);
-            let end = reader.position().try_into()?;
-            nodes[start as usize].outgoing.push(Edge {
-                data: &data[offset..end],
+            let offset = reader.offset();
+            let journey_count = Self::validate_edge(&mut reader, node_count)?;
+            edges.push((
+                offset..reader.offset(),
+                journey_total..journey_total + journey_count,
+            ));
+            journey_total += journey_count;
+        }
+        let mut trips: &[u8] = &[];
+        let mut change_times = FnvHashMap::default();
+        // flags of the sections found
+        let mut sections = 0;
+        while !reader.is_empty() {
+            let offset = reader.offset();
+            let tag = reader.u8()?;
+            let len = reader.u32()? as usize;
+            let section_offset = reader.offset();
+            let section = reader.bytes(len)?;
+            match tag {
+                SECTION_TRIPS => {
+                    if len != journey_total * 4 {
+                        return Err(GraphError::SectionLength { offset, tag, len });
+                    }
+                    trips = section;
+                    sections |= FLAG_TRIPS;
+                }
+                SECTION_CHANGE_TIMES => {
+                    if !len.is_multiple_of(6) {
+                        return Err(GraphError::SectionLength { offset, tag, len });
+                    }
+                    let mut entries = Reader::nested(section, section_offset);
+                    while !entries.is_empty() {
+                        let entry_offset = entries.offset();
+                        let node = entries.u32()?;
+                        let seconds = entries.u16()?;
+                        if node >= node_count {
+                            return Err(GraphError::MissingNode {
+                                offset: entry_offset,
+                                node,
+                            });
+                        }
+                        change_times.insert(node, chrono::Duration::seconds(seconds.into()));
+                    }
+                    sections |= FLAG_CHANGE_TIMES;
+                }
+                // sections unknown to this version are skipped
+                _ => {}
+            }
+        }
+        if let Some(metadata) = &metadata {
+            let missing = metadata.flags & !sections;
+            if missing != 0 {
+                return Err(GraphError::MissingSection { flags: missing });
+            }
+        }
+        for (range, journeys) in edges {
+            let edge = Edge {
+                data: &data[range],
+                trips: trips
+                    .get(journeys.start * 4..journeys.end * 4)
+                    .unwrap_or(&[]),
+            };
+            nodes[edge.start() as usize].outgoing.push(edge);
+            nodes[edge.end() as usize].incoming.push(edge);
+        }
+        Ok(Graph {
+            nodes,
+            ids,
+            change_times,
+            metadata,
+            has_trips: !trips.is_empty(),
+        })
+    }
+
+    fn read_node<'a>(reader: &mut Reader<'a>) -> Result<Node<'a>, GraphError> {
+        let start = reader.offset();
+        reader.u64()?;
+        let lat = reader.f32()?;
+        let lon = reader.f32()?;
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+            return Err(GraphError::Invalid {
+                offset: start + 8,
+                field: "coordinates",
             });
         }
-        Ok(Graph { nodes, ids })
+        let name_len = reader.u16()?;
+        let name_offset = reader.offset();
+        std::str::from_utf8(reader.bytes(name_len.into())?).map_err(|_| GraphError::Invalid {
+            offset: name_offset,
+            field: "name",
+        })?;
+        Ok(Node {
+            data: &reader.data()[start..reader.offset()],
+            outgoing: SmallVec::new(),
+            incoming: SmallVec::new(),
+        })
+    }
+
+    /// Validates the edge at the position of the reader and returns its journey count.
+    fn validate_edge(reader: &mut Reader<'_>, node_count: u32) -> Result<usize, GraphError> {
+        let offset = reader.offset();
+        for _ in 0..2 {
+            let node = reader.u32()?;
+            if node >= node_count {
+                return Err(GraphError::MissingNode { offset, node });
+            }
+        }
+        // walk seconds
+        reader.u16()?;
+        let journey_count = reader.u16()?.into();
+        let journeys_offset = reader.offset();
+        let journeys = reader.bytes(Edge::JOURNEY_SIZE * journey_count)?;
+        let period_bytes = reader.u16()?;
+        let periods_offset = reader.offset();
+        let period_count =
+            Self::validate_periods(reader.bytes(period_bytes.into())?, periods_offset)?;
+        for (i, journey) in journeys.chunks_exact(Edge::JOURNEY_SIZE).enumerate() {
+            let index = u16::from_le_bytes([journey[3], journey[4]]);
+            if usize::from(index) >= period_count {
+                return Err(GraphError::MissingPeriod {
+                    offset: journeys_offset + i * Edge::JOURNEY_SIZE,
+                    index,
+                });
+            }
+        }
+        Ok(journey_count)
+    }
+
+    /// Validates the operating periods of an edge starting at `offset` and returns their count.
+    fn validate_periods(data: &[u8], offset: usize) -> Result<usize, GraphError> {
+        let mut reader = Reader::nested(data, offset);
+        let mut count = 0;
+        while !reader.is_empty() {
+            let offset = reader.offset();
+            let start = decode_date(reader.u16()?).ok_or(GraphError::Invalid {
+                offset,
+                field: "period start",
+            })?;
+            let end = decode_date(reader.u16()?)
+                .filter(|end| *end >= start)
+                .ok_or(GraphError::Invalid {
+                    offset: offset + 2,
+                    field: "period end",
+                })?;
+            let days = usize::try_from((end - start).num_days()).unwrap_or_default() + 1;
+            let expected = days.div_ceil(8);
+            let actual = reader.u8()?.into();
+            if actual != expected {
+                return Err(GraphError::ValidDays {
+                    offset,
+                    expected,
+                    actual,
+                });
+            }
+            reader.bytes(actual)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Header of the graph, `None` for legacy graphs.
+    #[must_use]
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Time zone of the timetable, UTC for legacy graphs.
+    #[must_use]
+    pub fn timezone(&self) -> chrono_tz::Tz {
+        self.metadata
+            .as_ref()
+            .map_or(chrono_tz::UTC, |m| m.timezone)
+    }
+
+    /// Whether journeys carry trip ids, which are required to detect transfers.
+    #[must_use]
+    pub fn has_trips(&self) -> bool {
+        self.has_trips
     }
 }
 
 pub struct TimedNode<'a, 'b> {
     pub node: &'a Node<'b>,
     pub duration: chrono::Duration,
+    pub walking: Walking,
 }
 
 impl<'a, 'b> TimedNode<'a, 'b> {
     #[must_use]
     pub fn new(node: &'a Node<'b>, duration: chrono::Duration) -> Self {
-        TimedNode { node, duration }
+        Self::with_walking(node, duration, Walking::default())
+    }
+
+    #[must_use]
+    pub fn with_walking(node: &'a Node<'b>, duration: chrono::Duration, walking: Walking) -> Self {
+        TimedNode {
+            node,
+            duration,
+            walking,
+        }
     }
 
     /// Converts a `TimedNode` into a circle approximation.
@@ -254,21 +616,85 @@ impl<'a, 'b> TimedNode<'a, 'b> {
     /// If T cannot be cast to f32,
     #[must_use]
     pub fn to_points<T: GeoFloat + FromPrimitive>(&self) -> Vec<geo::Coord<T>> {
-        let distance = num_traits::cast::<f32, T>(MOVE_SPEED).unwrap()
-            * num_traits::cast::<i64, T>(self.duration.num_minutes()).unwrap();
+        self.walking.points(self.node.to_point(), self.duration)
+    }
+
+    #[must_use]
+    pub fn to_poly<T: GeoFloat + FromPrimitive>(&self) -> geo::Polygon<T> {
+        self.walking.poly(self.node.to_point(), self.duration)
+    }
+}
+
+/// Walking behaviour used for footpaths and the circles around reached nodes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Walking {
+    /// in m/min
+    pub speed: f32,
+    /// upper bound for the radius of the circles in m, unlimited if `None`
+    pub max_radius: Option<f32>,
+}
+
+impl Default for Walking {
+    fn default() -> Self {
+        Self {
+            speed: MOVE_SPEED,
+            max_radius: None,
+        }
+    }
+}
+
+impl Walking {
+    /// Distance in m, which can be walked within the duration.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn radius(&self, duration: chrono::Duration) -> f32 {
+        let radius = self.speed * duration.num_minutes() as f32;
+        self.max_radius.map_or(radius, |max| radius.min(max))
+    }
+
+    /// Time needed to walk the distance in m.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn duration(&self, distance: f32) -> chrono::Duration {
+        chrono::Duration::seconds((distance / self.speed * 60.0) as i64)
+    }
+
+    /// Rescales a walk of the graph, which assumes the default speed.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    fn scale(&self, walk: chrono::Duration) -> chrono::Duration {
+        if (self.speed - MOVE_SPEED).abs() < f32::EPSILON {
+            return walk;
+        }
+        chrono::Duration::seconds((walk.num_seconds() as f32 * MOVE_SPEED / self.speed) as i64)
+    }
+
+    /// Approximates the circle, which can be walked from the given point within the duration.
+    /// # Panics
+    /// If T cannot be cast to f32,
+    #[must_use]
+    pub fn points<T: GeoFloat + FromPrimitive>(
+        &self,
+        point: geo::Point<f32>,
+        duration: chrono::Duration,
+    ) -> Vec<geo::Coord<T>> {
         crate::vincenty::spherical_circle(
             geo::Point::from((
-                num_traits::cast(self.node.lon()).unwrap(),
-                num_traits::cast(self.node.lat()).unwrap(),
+                num_traits::cast(point.x()).unwrap(),
+                num_traits::cast(point.y()).unwrap(),
             )),
             8,
-            distance,
+            num_traits::cast::<f32, T>(self.radius(duration)).unwrap(),
         )
     }
 
+    /// Closed polygon of `points`.
     #[must_use]
-    pub fn to_poly<T: GeoFloat + FromPrimitive>(&self) -> geo::Polygon<T> {
-        let mut verts = self.to_points();
+    pub fn poly<T: GeoFloat + FromPrimitive>(
+        &self,
+        point: geo::Point<f32>,
+        duration: chrono::Duration,
+    ) -> geo::Polygon<T> {
+        let mut verts = self.points(point, duration);
         verts.push(verts[0]);
         let line_string = geo::LineString::new(verts);
         geo::Polygon::new(line_string, vec![])
@@ -303,9 +729,402 @@ impl rstar::RTreeObject for &TimedNode<'_, '_> {
     }
 }
 
+/// Spatial index over the nodes of a graph.
+pub struct NodeTree<'a, 'b> {
+    tree: RTree<GeomWithData<&'a Node<'b>, usize>>,
+}
+
+impl<'a, 'b> NodeTree<'a, 'b> {
+    #[must_use]
+    pub fn new(graph: &'a Graph<'b>) -> Self {
+        let entries = graph
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(idx, node)| GeomWithData::new(node, idx))
+            .collect();
+        Self {
+            tree: RTree::bulk_load(entries),
+        }
+    }
+
+    /// Returns seeds for all nodes, which can be walked to from the given point
+    /// within the duration. The offset of each seed is the walking time.
+    #[must_use]
+    pub fn seeds_around(
+        &self,
+        point: geo::Point<f32>,
+        duration: chrono::Duration,
+        walking: &Walking,
+    ) -> Vec<Seed> {
+        let radius = walking.radius(duration);
+        self.tree
+            .locate_in_envelope_intersecting(&envelope_around(point, radius))
+            .filter_map(|entry| {
+                let distance = Haversine.distance(point, entry.geom().to_point());
+                if distance > radius {
+                    return None;
+                }
+                Some(Seed::with_offset(entry.data, walking.duration(distance)))
+            })
+            .collect()
+    }
+}
+
+/// Bounding box of the circle with the radius in m around the point.
+pub(crate) fn envelope_around(point: geo::Point<f32>, radius: f32) -> rstar::AABB<[f32; 2]> {
+    const METERS_PER_DEGREE: f32 = 111_320.0;
+    let lat_delta = radius / METERS_PER_DEGREE;
+    let lon_delta = radius / (METERS_PER_DEGREE * point.y().to_radians().cos().max(0.01));
+    rstar::AABB::from_corners(
+        [point.x() - lon_delta, point.y() - lat_delta],
+        [point.x() + lon_delta, point.y() + lat_delta],
+    )
+}
+
+/// Node from which a search starts.
+#[derive(Clone, Copy, Debug)]
+pub struct Seed {
+    pub node: usize,
+    /// time already spent before reaching the node
+    pub offset: chrono::Duration,
+}
+
+impl Seed {
+    #[must_use]
+    pub fn new(node: usize) -> Self {
+        Self {
+            node,
+            offset: chrono::Duration::zero(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_offset(node: usize, offset: chrono::Duration) -> Self {
+        Self { node, offset }
+    }
+}
+
+/// Outcome of a single search, which is valid until the next search.
+pub(crate) struct Reached<'a, 'b, 'w> {
+    graph: &'a Graph<'b>,
+    walking: Walking,
+    workspace: &'w Workspace,
+    /// predecessors of the labels, if traced
+    pub trace: Option<Trace>,
+}
+
+impl<'a, 'b> Reached<'a, 'b, '_> {
+    /// Nodes whose circles are not covered by the circle of their predecessor.
+    pub fn nodes(&self) -> impl Iterator<Item = TimedNode<'a, 'b>> + '_ {
+        self.workspace.circles().map(|(idx, remaining)| {
+            TimedNode::with_walking(&self.graph.nodes[idx as usize], remaining, self.walking)
+        })
+    }
+
+    /// Indices of the nodes returned by `nodes`.
+    pub fn node_indices(&self) -> impl Iterator<Item = u32> + '_ {
+        self.workspace.circles().map(|(idx, _)| idx)
+    }
+
+    /// Travel time to every reached node.
+    pub fn durations(&self) -> impl Iterator<Item = (u32, chrono::Duration)> + '_ {
+        self.workspace.arrivals()
+    }
+
+    pub fn duration(&self, node: u32) -> Option<chrono::Duration> {
+        self.workspace.arrival(node)
+    }
+}
+
+/// Direction in which the timetable is searched.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Direction {
+    /// Departs at the start time and follows outgoing edges.
+    #[default]
+    Forward,
+    /// Arrives by the start time and follows incoming edges backwards.
+    Reverse,
+}
+
+/// Rules applied when changing between vehicles.
+/// Transfers can only be detected, if the graph has trips.
+#[derive(Clone, Copy, Debug)]
+pub struct SearchOptions {
+    /// minimum time between arriving with one vehicle and departing with another
+    pub min_change: chrono::Duration,
+    /// use the minimum change times of the graph instead of `min_change` where available
+    pub station_change_times: bool,
+    /// added to the travel time for every boarded vehicle
+    pub boarding_penalty: chrono::Duration,
+    /// maximum number of changes between vehicles, unlimited if `None`
+    pub max_transfers: Option<u8>,
+    /// speed for walks between nodes and radius of the final walk
+    pub walking: Walking,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            min_change: chrono::Duration::zero(),
+            station_change_times: true,
+            boarding_penalty: chrono::Duration::zero(),
+            max_transfers: None,
+            walking: Walking::default(),
+        }
+    }
+}
+
+/// Journey taken along an edge.
+struct Ride<'a> {
+    journey: Journey<'a>,
+    /// elapsed timetable duration when leaving the first node in search direction
+    leave: chrono::Duration,
+    /// elapsed timetable duration when reaching the second node in search direction
+    reach: chrono::Duration,
+}
+
+/// State of the search at a node.
+struct Label {
+    /// travel time including penalties
+    elapsed: chrono::Duration,
+    /// penalties included in elapsed, which do not shift the timetable
+    penalty: chrono::Duration,
+    node: u32,
+    /// vehicle the node was reached with, if the search continues on board
+    trip: Option<u32>,
+    /// number of boarded vehicles, only counted if transfers are limited
+    boardings: u8,
+}
+
+/// Entry of the search queue.
+pub(crate) struct Queued {
+    /// elapsed time plus a lower bound of the time to the target
+    priority: chrono::Duration,
+    label: Label,
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.priority.cmp(&other.priority)
+    }
+}
+
+/// Node a point-to-point search stops at once its earliest arrival is known.
+struct Goal<'a, 'b> {
+    node: u32,
+    nodes: &'a [Node<'b>],
+    /// fastest speed towards the node in m/s, orders the queue like A* if given
+    speed: Option<f32>,
+}
+
+impl Goal<'_, '_> {
+    /// Time needed to reach the goal from the node at the fastest speed.
+    fn lower_bound(&self, node: u32) -> chrono::Duration {
+        let Some(speed) = self.speed else {
+            return chrono::Duration::zero();
+        };
+        let distance = Haversine.distance(
+            self.nodes[node as usize].to_point(),
+            self.nodes[self.node as usize].to_point(),
+        );
+        // rounding down keeps the bound admissible
+        #[allow(clippy::cast_possible_truncation)]
+        chrono::Duration::milliseconds((distance / speed * 1000.0) as i64)
+    }
+}
+
+struct SearchState<'a, 'b, 'w> {
+    duration: chrono::Duration,
+    walking: Walking,
+    /// physical arrivals, circles, best labels per node, trip and boardings and the queue.
+    /// Labels without trip may walk or board any vehicle, labels with trip may only stay on board.
+    workspace: &'w mut Workspace,
+    /// predecessors of the labels, only recorded if routes are requested
+    trace: Option<Trace>,
+    goal: Option<Goal<'a, 'b>>,
+}
+
+impl<'b, 'w> SearchState<'_, 'b, 'w> {
+    fn new(
+        workspace: &'w mut Workspace,
+        nodes: usize,
+        duration: chrono::Duration,
+        walking: Walking,
+    ) -> Self {
+        workspace.reset(nodes);
+        Self {
+            duration,
+            walking,
+            workspace,
+            trace: None,
+            goal: None,
+        }
+    }
+
+    /// Whether the earliest arrival at the goal cannot improve anymore,
+    /// because every queued label has a larger priority.
+    fn reached_goal(&self, priority: chrono::Duration) -> bool {
+        self.goal.as_ref().is_some_and(|goal| {
+            self.workspace
+                .arrival(goal.node)
+                .is_some_and(|d| d <= priority)
+        })
+    }
+
+    /// Whether a label with less or equal boardings is at least as fast.
+    fn fewer_boardings_as_fast(&self, label: &Label) -> bool {
+        (0..label.boardings).any(|b| {
+            self.workspace
+                .label((label.node, label.trip, b))
+                .is_some_and(|e| e <= label.elapsed)
+        })
+    }
+
+    fn is_stale(&self, label: &Label) -> bool {
+        self.workspace
+            .label((label.node, label.trip, label.boardings))
+            .is_some_and(|e| e < label.elapsed)
+            || self.fewer_boardings_as_fast(label)
+    }
+
+    /// Records the arrival at `node` with the label identified by `key` and adds it
+    /// to the result, if its circle is not covered by the circle of its predecessor.
+    fn arrive(
+        &mut self,
+        from: &Node<'b>,
+        from_elapsed: chrono::Duration,
+        node: &Node<'b>,
+        key: LabelKey,
+        elapsed: chrono::Duration,
+    ) {
+        let idx = key.0;
+        if self.workspace.arrival(idx).is_some_and(|d| d <= elapsed) {
+            return;
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.arrivals.insert(idx, key);
+        }
+        let distance = Haversine.distance(from.to_point(), node.to_point());
+        let from_radius = self.walking.radius(self.duration - from_elapsed);
+        let remaining = self.duration - elapsed;
+        let radius = self.walking.radius(remaining);
+        if distance + radius > from_radius {
+            self.workspace.set_circle(idx, remaining);
+        }
+        self.workspace.set_arrival(idx, elapsed);
+    }
+
+    /// Starts the search at the node after the offset has passed.
+    fn seed(&mut self, idx: u32, offset: chrono::Duration) {
+        if offset > self.duration
+            || offset < chrono::Duration::zero()
+            || self.workspace.arrival(idx).is_some_and(|d| d <= offset)
+        {
+            return;
+        }
+        self.workspace.set_circle(idx, self.duration - offset);
+        self.workspace.set_arrival(idx, offset);
+        if let Some(trace) = &mut self.trace {
+            trace.arrivals.insert(idx, (idx, None, 0));
+        }
+        self.push(
+            Label {
+                elapsed: offset,
+                penalty: chrono::Duration::zero(),
+                node: idx,
+                trip: None,
+                boardings: 0,
+            },
+            Step::Seed { offset },
+        );
+    }
+
+    /// Walks from the node of the current label to `node`.
+    fn walk(
+        &mut self,
+        from: &Node<'b>,
+        current: &Label,
+        node: &Node<'b>,
+        idx: u32,
+        walk: chrono::Duration,
+    ) {
+        let elapsed = current.elapsed + walk;
+        if elapsed > self.duration {
+            return;
+        }
+        self.arrive(
+            from,
+            current.elapsed,
+            node,
+            (idx, None, current.boardings),
+            elapsed,
+        );
+        let leave = current.elapsed - current.penalty;
+        self.push(
+            Label {
+                elapsed,
+                penalty: current.penalty,
+                node: idx,
+                trip: None,
+                boardings: current.boardings,
+            },
+            Step::Walk {
+                from: (current.node, current.trip, current.boardings),
+                leave,
+                reach: leave + walk,
+            },
+        );
+    }
+
+    /// Queues the label unless it exceeds the duration or is dominated.
+    fn push(&mut self, label: Label, step: Step) {
+        let key = (label.node, label.trip, label.boardings);
+        if label.elapsed > self.duration
+            || self
+                .workspace
+                .label(key)
+                .is_some_and(|e| e <= label.elapsed)
+            || self.fewer_boardings_as_fast(&label)
+        {
+            return;
+        }
+        self.workspace.set_label(key, label.elapsed);
+        if let Some(trace) = &mut self.trace {
+            trace.steps.insert(key, step);
+        }
+        let bound = self.goal.as_ref().map_or(chrono::Duration::zero(), |goal| {
+            goal.lower_bound(label.node)
+        });
+        self.workspace.push(Queued {
+            priority: label.elapsed + bound,
+            label,
+        });
+    }
+}
+
+/// Dijkstra search over the timetable.
+/// Start times are local times in the time zone of the graph.
 pub struct IsochroneDijsktra<'a, 'b> {
     graph: &'a Graph<'b>,
     periods: Vec<OperatingPeriod<'b>>,
+    direction: Direction,
+    options: SearchOptions,
+    workspace: Workspace,
 }
 
 impl<'a, 'b: 'a> IsochroneDijsktra<'a, 'b> {
@@ -314,141 +1133,796 @@ impl<'a, 'b: 'a> IsochroneDijsktra<'a, 'b> {
         Self {
             graph,
             periods: Vec::new(),
+            direction: Direction::Forward,
+            options: SearchOptions::default(),
+            workspace: Workspace::new(),
         }
     }
 
-    #[allow(clippy::cast_lossless)]
-    fn u16_to_time(number: u16) -> NaiveTime {
-        let minute = number % 60;
-        let hour = number / 60;
-        NaiveTime::from_hms_opt(hour as u32, minute as u32, 0).unwrap()
+    /// Uses the memory of an earlier search, which avoids allocations
+    /// when searches are not repeated with the same `IsochroneDijsktra`.
+    #[must_use]
+    pub fn with_workspace(mut self, workspace: Workspace) -> Self {
+        self.workspace = workspace;
+        self
     }
 
-    fn u16_to_date(number: u16) -> NaiveDate {
-        let year = number & 0b_0000_0000_0111_1111;
-        let month = (number >> 7) & 0b_0000_0000_0000_1111;
-        let day = (number >> 11) & 0b_0000_0000_0001_1111;
-        NaiveDate::from_ymd_opt(i32::from(year) + 2000, month.into(), day.into()).unwrap()
+    /// Hands out the memory of the searches for reuse by another `IsochroneDijsktra`.
+    pub fn take_workspace(&mut self) -> Workspace {
+        std::mem::take(&mut self.workspace)
+    }
+
+    #[must_use]
+    pub fn graph(&self) -> &'a Graph<'b> {
+        self.graph
+    }
+
+    /// Sets the direction of the search.
+    /// With `Direction::Reverse` the start time of `nodes_within` is the arrival time
+    /// and reached nodes are those from which the start node can be reached in time.
+    #[must_use]
+    pub fn with_direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Sets the rules for changing between vehicles and walking.
+    #[must_use]
+    pub fn with_options(mut self, options: SearchOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    #[must_use]
+    pub fn options(&self) -> &SearchOptions {
+        &self.options
     }
 
     fn valid_on(period: &OperatingPeriod<'b>, date: NaiveDate) -> Result<bool, Error> {
-        let start = Self::u16_to_date(period.start());
-        let end = Self::u16_to_date(period.end());
-        if date < start || date > end {
-            return Ok(false);
-        }
-        let diff = date - start;
-        let days: usize = diff.num_days().try_into()?;
-        let idx = days / 8;
-        let off = days % 8;
-        Ok((1 << off & period.valid_days()[idx]) > 0)
+        Ok(period.operates_on(date).ok_or("invalid operating period")?)
     }
 
+    /// Finds the earliest journey departing between start and latest.
+    /// If a trip is given only journeys of that trip are considered.
+    /// Returns the journey with its departure and arrival.
     fn next_journey(
         &mut self,
         edge: &Edge<'b>,
         start: NaiveDateTime,
-    ) -> Result<Option<Journey<'b>>, Error> {
-        let mut departure = NaiveTime::from_hms_opt(23, 59, 59).unwrap();
-        let mut result = None;
+        latest: NaiveDateTime,
+        trip: Option<u32>,
+    ) -> Result<Option<(Journey<'b>, NaiveDateTime, NaiveDateTime)>, Error> {
+        let mut result: Option<(Journey<'b>, NaiveDateTime, NaiveDateTime)> = None;
         self.periods.extend(edge.operating_periods());
         for journey in edge.journeys() {
-            let current_departure = Self::u16_to_time(journey.departure());
-            if current_departure > departure || current_departure < start.time() {
+            if trip.is_some() && journey.trip() != trip {
                 continue;
             }
-            let period = &self.periods[journey.operating_period_index() as usize];
-            if !Self::valid_on(period, start.date())? {
+            for date in service_dates(start, latest) {
+                let (departure, arrival) = journey.times_on(date);
+                if departure < start
+                    || departure > latest
+                    || result.is_some_and(|(_, best, _)| best <= departure)
+                {
+                    continue;
+                }
+                let period = &self.periods[journey.operating_period_index() as usize];
+                if Self::valid_on(period, date)? {
+                    result = Some((journey, departure, arrival));
+                }
+            }
+        }
+        self.periods.clear();
+        Ok(result)
+    }
+
+    /// Finds the latest journey arriving between earliest and end.
+    /// If a trip is given only journeys of that trip are considered.
+    /// Returns the journey with its departure and arrival.
+    fn prev_journey(
+        &mut self,
+        edge: &Edge<'b>,
+        end: NaiveDateTime,
+        earliest: NaiveDateTime,
+        trip: Option<u32>,
+    ) -> Result<Option<(Journey<'b>, NaiveDateTime, NaiveDateTime)>, Error> {
+        let mut result: Option<(Journey<'b>, NaiveDateTime, NaiveDateTime)> = None;
+        self.periods.extend(edge.operating_periods());
+        for journey in edge.journeys() {
+            if trip.is_some() && journey.trip() != trip {
                 continue;
             }
-            departure = current_departure;
-            result = Some(journey);
+            for date in service_dates(earliest, end) {
+                let (departure, arrival) = journey.times_on(date);
+                if arrival > end
+                    || arrival < earliest
+                    || result.is_some_and(|(_, _, best)| best >= arrival)
+                {
+                    continue;
+                }
+                let period = &self.periods[journey.operating_period_index() as usize];
+                if Self::valid_on(period, date)? {
+                    result = Some((journey, departure, arrival));
+                }
+            }
         }
         self.periods.clear();
         Ok(result)
     }
 
     #[allow(clippy::cast_lossless)]
-    fn get_walk(edge: &Edge<'b>) -> Option<chrono::Duration> {
+    fn get_walk(&self, edge: &Edge<'b>) -> Option<chrono::Duration> {
         let walk = edge.walk();
         if walk == u16::MAX {
             return None;
         }
-        Some(chrono::Duration::seconds(walk as i64))
+        Some(
+            self.options
+                .walking
+                .scale(chrono::Duration::seconds(walk as i64)),
+        )
     }
 
-    fn next_journey_duration(
+    /// Finds the journey along the edge after `elapsed` has passed in search direction,
+    /// which does not exceed `horizon`.
+    fn ride(
         &mut self,
         edge: &Edge<'b>,
         start: NaiveDateTime,
-    ) -> Result<Option<chrono::Duration>, Error> {
-        let opt_journey = self.next_journey(edge, start)?;
-        if opt_journey.is_none() {
-            return Ok(None);
+        elapsed: chrono::Duration,
+        horizon: chrono::Duration,
+        trip: Option<u32>,
+    ) -> Result<Option<Ride<'b>>, Error> {
+        Ok(match self.direction {
+            Direction::Forward => self
+                .next_journey(edge, start + elapsed, start + horizon, trip)?
+                .map(|(journey, departure, arrival)| Ride {
+                    journey,
+                    leave: departure - start,
+                    reach: arrival - start,
+                }),
+            Direction::Reverse => self
+                .prev_journey(edge, start - elapsed, start - horizon, trip)?
+                .map(|(journey, departure, arrival)| Ride {
+                    journey,
+                    leave: start - arrival,
+                    reach: start - departure,
+                }),
+        })
+    }
+
+    /// Whether the search distinguishes staying on board from changing vehicles.
+    /// # Errors
+    /// If transfer rules are requested, but the graph has no trips.
+    fn models_transfers(&self) -> Result<bool, Error> {
+        let requested = self.options.min_change > chrono::Duration::zero()
+            || self.options.boarding_penalty > chrono::Duration::zero()
+            || self.options.max_transfers.is_some();
+        if requested && !self.graph.has_trips() {
+            return Err("graph has no trips to detect transfers".into());
         }
-        let journey = opt_journey.unwrap();
-        let arrival = Self::u16_to_time(journey.arrival());
-        let duration = arrival - start.time();
-        if duration >= chrono::Duration::zero() {
-            return Ok(Some(duration));
+        let station = self.options.station_change_times && !self.graph.change_times.is_empty();
+        Ok(self.graph.has_trips() && (requested || station))
+    }
+
+    fn change_time(&self, idx: u32) -> chrono::Duration {
+        if self.options.station_change_times {
+            if let Some(change) = self.graph.change_times.get(&idx) {
+                return *change;
+            }
         }
-        // travel over midnight
-        let midnight = NaiveTime::from_hms_opt(23, 59, 59).unwrap();
-        let pre_midnight = midnight - start.time();
-        let post_midnight = arrival - NaiveTime::MIN;
-        Ok(Some(pre_midnight + post_midnight))
+        self.options.min_change
     }
 
     /// Computes reachable nodes.
+    /// Depending on the direction `start` is either the departure or the arrival time.
     /// # Errors
     /// If underlying data is invalid.
-    #[allow(clippy::cast_precision_loss)]
     pub fn nodes_within(
         &mut self,
         node_idx: usize,
         start: NaiveDateTime,
         duration: chrono::Duration,
     ) -> Result<Vec<TimedNode<'a, 'b>>, Error> {
-        let mut result: FnvHashMap<u32, TimedNode<'a, 'b>> = FnvHashMap::default();
-        let node = &self.graph.nodes[node_idx];
-        result.insert(node_idx.try_into()?, TimedNode::new(node, duration));
-        let mut arrivals = FnvHashMap::<u32, NaiveDateTime>::default();
-        arrivals.insert(node_idx.try_into()?, start);
-        let mut heap = rudac::heap::FibonacciHeap::<TimedNode<'a, 'b>>::init_min();
-        let max_time = start + duration;
-        heap.push(TimedNode::new(node, chrono::Duration::zero()));
-        while let Some(current) = heap.pop() {
-            let departure = start + current.duration;
-            for out in &current.node.outgoing {
-                let opt_walk = Self::get_walk(out);
-                let opt_journey = self.next_journey_duration(out, departure)?;
-                let out_duration = match (opt_walk, opt_journey) {
-                    (None, None) => continue,
-                    (None, Some(j)) => j,
-                    (Some(w), None) => w,
-                    (Some(w), Some(j)) => w.min(j),
+        self.nodes_within_multi(&[Seed::new(node_idx)], start, duration)
+    }
+
+    /// Computes reachable nodes for several nested durations with a single search.
+    /// Returns the nodes of each band in the order of `bands`. The duration of a
+    /// node is the time remaining within its band.
+    /// # Errors
+    /// If underlying data is invalid.
+    pub fn nodes_within_bands(
+        &mut self,
+        seeds: &[Seed],
+        start: NaiveDateTime,
+        bands: &[chrono::Duration],
+    ) -> Result<Vec<Vec<TimedNode<'a, 'b>>>, Error> {
+        let Some(max) = bands.iter().max().copied() else {
+            return Ok(Vec::new());
+        };
+        let reached = self.nodes_within_multi(seeds, start, max)?;
+        // circles shrink by the same radius for every node of a band,
+        // so nodes pruned for the largest band are pruned for smaller ones as well
+        Ok(bands
+            .iter()
+            .map(|band| {
+                let shift = max - *band;
+                reached
+                    .iter()
+                    .filter(|n| n.duration >= shift)
+                    .map(|n| TimedNode::with_walking(n.node, n.duration - shift, n.walking))
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Computes nodes reachable from any of the given seeds in a single search.
+    /// Depending on the direction `start` is either the departure or the arrival time.
+    /// # Errors
+    /// If underlying data is invalid.
+    pub fn nodes_within_multi(
+        &mut self,
+        seeds: &[Seed],
+        start: NaiveDateTime,
+        duration: chrono::Duration,
+    ) -> Result<Vec<TimedNode<'a, 'b>>, Error> {
+        Ok(self.search(seeds, start, duration)?.nodes().collect())
+    }
+
+    /// Computes the travel time to every node reachable from the seeds within the duration.
+    /// # Errors
+    /// If underlying data is invalid.
+    pub fn travel_times(
+        &mut self,
+        seeds: &[Seed],
+        start: NaiveDateTime,
+        duration: chrono::Duration,
+    ) -> Result<FnvHashMap<u32, chrono::Duration>, Error> {
+        Ok(self.search(seeds, start, duration)?.durations().collect())
+    }
+
+    /// Computes the routes to all nodes reachable from the seeds within the duration.
+    /// # Errors
+    /// If underlying data is invalid.
+    pub fn routes(
+        &mut self,
+        seeds: &[Seed],
+        start: NaiveDateTime,
+        duration: chrono::Duration,
+    ) -> Result<Routes, Error> {
+        let direction = self.direction;
+        let reached = self.search_traced(seeds, start, duration, true, None)?;
+        Ok(Routes::new(
+            start,
+            direction,
+            reached.trace.unwrap_or_default(),
+        ))
+    }
+
+    /// Computes the earliest arrival at the target and its route, stopping as soon as
+    /// it is known instead of exploring the whole duration. With `max_speed` in km/h the
+    /// search prefers nodes closer to the target. The speed has to be at least that of
+    /// the fastest vehicle and walk, otherwise a later arrival may be returned.
+    /// Returns `None` if the target is not reached within the duration.
+    /// # Errors
+    /// If the target does not exist, the speed is not positive or underlying data is invalid.
+    pub fn connection(
+        &mut self,
+        seeds: &[Seed],
+        target: usize,
+        start: NaiveDateTime,
+        duration: chrono::Duration,
+        max_speed: Option<f32>,
+    ) -> Result<Option<Connection>, Error> {
+        if target >= self.graph.nodes.len() {
+            return Err("target does not exist".into());
+        }
+        if max_speed.is_some_and(|speed| speed.is_nan() || speed <= 0.0) {
+            return Err("max speed must be positive".into());
+        }
+        let node = target.try_into()?;
+        let goal = Goal {
+            node,
+            nodes: &self.graph.nodes,
+            // km/h to m/s
+            speed: max_speed.map(|speed| speed / 3.6),
+        };
+        let direction = self.direction;
+        let reached = self.search_traced(seeds, start, duration, true, Some(goal))?;
+        let Some(duration) = reached.duration(node) else {
+            return Ok(None);
+        };
+        let routes = Routes::new(start, direction, reached.trace.unwrap_or_default());
+        Ok(routes
+            .route(target)
+            .map(|route| Connection { duration, route }))
+    }
+
+    pub(crate) fn search(
+        &mut self,
+        seeds: &[Seed],
+        start: NaiveDateTime,
+        duration: chrono::Duration,
+    ) -> Result<Reached<'a, 'b, '_>, Error> {
+        self.search_traced(seeds, start, duration, false, None)
+    }
+
+    /// Runs a search in the workspace, which holds the result until the next search.
+    fn search_traced(
+        &mut self,
+        seeds: &[Seed],
+        start: NaiveDateTime,
+        duration: chrono::Duration,
+        trace: bool,
+        goal: Option<Goal<'a, 'b>>,
+    ) -> Result<Reached<'a, 'b, '_>, Error> {
+        let mut workspace = self.take_workspace();
+        let trace = self.explore(&mut workspace, seeds, start, duration, trace, goal);
+        self.workspace = workspace;
+        Ok(Reached {
+            graph: self.graph,
+            walking: self.options.walking,
+            workspace: &self.workspace,
+            trace: trace?,
+        })
+    }
+
+    /// Prepares a search starting at the seeds.
+    fn seeded<'w>(
+        &self,
+        workspace: &'w mut Workspace,
+        seeds: &[Seed],
+        duration: chrono::Duration,
+        trace: bool,
+        goal: Option<Goal<'a, 'b>>,
+    ) -> Result<SearchState<'a, 'b, 'w>, Error> {
+        let mut state = SearchState::new(
+            workspace,
+            self.graph.nodes.len(),
+            duration,
+            self.options.walking,
+        );
+        if trace {
+            state.trace = Some(Trace::default());
+        }
+        state.goal = goal;
+        for seed in seeds {
+            if seed.node >= self.graph.nodes.len() {
+                return Err("seed does not exist".into());
+            }
+            state.seed(seed.node.try_into()?, seed.offset);
+        }
+        Ok(state)
+    }
+
+    /// Labels the nodes reachable from the seeds and returns the trace, if requested.
+    fn explore(
+        &mut self,
+        workspace: &mut Workspace,
+        seeds: &[Seed],
+        start: NaiveDateTime,
+        duration: chrono::Duration,
+        trace: bool,
+        goal: Option<Goal<'a, 'b>>,
+    ) -> Result<Option<Trace>, Error> {
+        let transfers = self.models_transfers()?;
+        let boardings_limit = self.options.max_transfers.map(|t| t.saturating_add(1));
+        let mut state = self.seeded(workspace, seeds, duration, trace, goal)?;
+        while let Some(Queued {
+            priority,
+            label: current,
+        }) = state.workspace.pop()
+        {
+            if state.reached_goal(priority) {
+                break;
+            }
+            if state.is_stale(&current) {
+                continue;
+            }
+            let current_key = (current.node, current.trip, current.boardings);
+            let node = &self.graph.nodes[current.node as usize];
+            let edges = match self.direction {
+                Direction::Forward => &node.outgoing,
+                Direction::Reverse => &node.incoming,
+            };
+            let timetable_elapsed = current.elapsed - current.penalty;
+            for out in edges {
+                let out_idx = match self.direction {
+                    Direction::Forward => out.end(),
+                    Direction::Reverse => out.start(),
                 };
-                let total_duration = current.duration + out_duration;
-                let arrival = start + total_duration;
-                if arrival > max_time {
-                    continue;
+                let out_node = &self.graph.nodes[out_idx as usize];
+                if let Some(mut walk) = self.get_walk(out) {
+                    // the walk may lead to another vehicle, so leaving one on foot
+                    // takes at least the change time
+                    if current.trip.is_some() {
+                        walk = walk.max(self.change_time(current.node));
+                    }
+                    state.walk(node, &current, out_node, out_idx, walk);
                 }
-                let stored_arrival = arrivals.get(&out.end()).unwrap_or(&NaiveDateTime::MAX);
-                if arrival < *stored_arrival {
-                    let out_node = &self.graph.nodes[out.end() as usize];
-                    let distance = Haversine.distance(current.node.to_point(), out_node.to_point());
-                    let current_radius =
-                        MOVE_SPEED * (duration - current.duration).num_minutes() as f32;
-                    let out_remaining = duration - total_duration;
-                    let out_radius = MOVE_SPEED * (out_remaining).num_minutes() as f32;
-                    if distance + out_radius > current_radius {
-                        result.insert(out.end(), TimedNode::new(out_node, out_remaining));
+                let boarding = current.trip.is_none();
+                let mut boardings = current.boardings;
+                if let Some(limit) = boardings_limit {
+                    if boarding {
+                        if boardings >= limit {
+                            continue;
+                        }
+                        boardings += 1;
                     }
-                    arrivals.insert(out.end(), arrival);
-                    heap.push(TimedNode::new(out_node, total_duration));
+                }
+                let Some(ride) = self.ride(
+                    out,
+                    start,
+                    timetable_elapsed,
+                    duration - current.penalty,
+                    current.trip,
+                )?
+                else {
+                    continue;
+                };
+                let mut total_duration = current.elapsed + (ride.reach - timetable_elapsed);
+                let mut penalty = current.penalty;
+                if transfers && boarding {
+                    total_duration += self.options.boarding_penalty;
+                    penalty += self.options.boarding_penalty;
+                }
+                if total_duration > duration {
+                    continue;
+                }
+                let trip = ride.journey.trip();
+                let ride_step = Step::Ride {
+                    from: current_key,
+                    trip,
+                    leave: ride.leave,
+                    reach: ride.reach,
+                };
+                let mut ready = Label {
+                    elapsed: total_duration,
+                    penalty,
+                    node: out_idx,
+                    trip: None,
+                    boardings,
+                };
+                if let (Some(trip), true) = (trip, transfers) {
+                    let on_board = (out_idx, Some(trip), boardings);
+                    state.arrive(node, current.elapsed, out_node, on_board, total_duration);
+                    state.push(
+                        Label {
+                            trip: Some(trip),
+                            ..ready
+                        },
+                        ride_step,
+                    );
+                    ready.elapsed += self.change_time(out_idx);
+                    state.push(ready, Step::Change { from: on_board });
+                } else {
+                    let key = (out_idx, None, boardings);
+                    state.arrive(node, current.elapsed, out_node, key, total_duration);
+                    state.push(ready, ride_step);
                 }
             }
         }
-        Ok(result.into_values().collect())
+        Ok(state.trace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::{Direction, Graph, IsochroneDijsktra, Journey, SearchOptions, Seed, SECTION_TRIPS};
+    use crate::{
+        builder::GraphBuilder,
+        error::GraphError,
+        testing::{at, date, Layout, JOURNEY_PERIOD, PERIOD_DAYS, SECTION_LEN},
+    };
+
+    /// Builds stations A, B and C, which are too far apart to walk, with the given journeys
+    /// as start, end, departure, arrival and the single service day of their period.
+    fn overnight(journeys: &[(u32, u32, u16, u16, u32)]) -> Vec<u8> {
+        let mut builder = GraphBuilder::new();
+        for (id, name) in ["A", "B", "C"].into_iter().enumerate() {
+            #[allow(clippy::cast_precision_loss)]
+            builder
+                .add_node(id as u64, 50.0 + id as f32, 8.0, name)
+                .unwrap();
+        }
+        for (start, end, departure, arrival, day) in journeys {
+            let period = builder.add_period(date(1), date(31), [date(*day)]).unwrap();
+            builder
+                .add_journey(*start, *end, *departure, *arrival, period, None)
+                .unwrap();
+        }
+        builder.to_bytes().unwrap()
+    }
+
+    #[test]
+    fn departure_after_midnight_of_previous_service_day() {
+        // 25:10 on the 8th departs at 01:10 on the 9th
+        let data = overnight(&[(0, 1, 1510, 1520, 8)]);
+        let graph = Graph::from_slice(&data).unwrap();
+        let times = IsochroneDijsktra::new(&graph)
+            .travel_times(&[Seed::new(0)], at(9, 0, 30), Duration::hours(1))
+            .unwrap();
+        assert_eq!(times.get(&1), Some(&Duration::minutes(50)));
+    }
+
+    #[test]
+    fn late_start_reaches_trains_after_midnight() {
+        // the same night as 24:40 of the 8th and as 00:40 of the 9th
+        let data = overnight(&[(0, 1, 1480, 1500, 8), (0, 2, 40, 70, 9)]);
+        let graph = Graph::from_slice(&data).unwrap();
+        let times = IsochroneDijsktra::new(&graph)
+            .travel_times(&[Seed::new(0)], at(8, 22, 30), Duration::hours(3))
+            .unwrap();
+        assert_eq!(times.get(&1), Some(&Duration::minutes(150)));
+        assert_eq!(times.get(&2), Some(&Duration::minutes(160)));
+    }
+
+    #[test]
+    fn wrapped_arrival_of_legacy_graph() {
+        // arrival 00:20, departure 23:50 and operating period 0
+        let data = [20, 0x05, 0x96, 0, 0];
+        let journey = Journey {
+            data: &data,
+            trip: None,
+        };
+        assert_eq!((journey.departure(), journey.arrival()), (1430, 20));
+        assert_eq!(journey.times_on(date(8)), (at(8, 23, 50), at(9, 0, 20)));
+    }
+
+    #[test]
+    fn reverse_search_over_midnight() {
+        let data = overnight(&[(0, 1, 1510, 1520, 8), (2, 1, 1420, 1450, 8)]);
+        let graph = Graph::from_slice(&data).unwrap();
+        let times = IsochroneDijsktra::new(&graph)
+            .with_direction(Direction::Reverse)
+            .travel_times(&[Seed::new(1)], at(9, 1, 30), Duration::hours(3))
+            .unwrap();
+        // departing at 01:10 of the 9th and at 23:40 of the 8th
+        assert_eq!(times.get(&0), Some(&Duration::minutes(20)));
+        assert_eq!(times.get(&2), Some(&Duration::minutes(110)));
+    }
+
+    /// Builds `count` stations too far apart to walk with the given journeys
+    /// as trip, start, end, departure and arrival on the 8th.
+    fn with_trips(count: u32, journeys: &[(u32, u32, u32, u16, u16)]) -> GraphBuilder {
+        let mut builder = GraphBuilder::new();
+        for id in 0..count {
+            #[allow(clippy::cast_precision_loss)]
+            builder
+                .add_node(id.into(), 50.0 + id as f32, 8.0, &id.to_string())
+                .unwrap();
+        }
+        let period = builder.add_period(date(1), date(31), [date(8)]).unwrap();
+        for (trip, start, end, departure, arrival) in journeys {
+            builder
+                .add_journey(*start, *end, *departure, *arrival, period, Some(*trip))
+                .unwrap();
+        }
+        builder
+    }
+
+    /// Builds stations A to E, where C is a minute's walk from B, with trips
+    /// A-B at 08:00-08:10, B-D at 08:12-08:20 and B-E at 08:16-08:25.
+    fn transfers(change: Option<Duration>) -> Vec<u8> {
+        let mut builder = with_trips(
+            5,
+            &[
+                (1, 0, 1, 480, 490),
+                (3, 1, 3, 492, 500),
+                (4, 1, 4, 496, 505),
+            ],
+        );
+        builder.add_walk(1, 2, Duration::minutes(1)).unwrap();
+        builder.add_walk(2, 1, Duration::minutes(1)).unwrap();
+        if let Some(change) = change {
+            builder.set_change_time(1, change).unwrap();
+        }
+        builder.to_bytes().unwrap()
+    }
+
+    fn travel_times(graph: &Graph<'_>, options: SearchOptions) -> fnv::FnvHashMap<u32, Duration> {
+        IsochroneDijsktra::new(graph)
+            .with_options(options)
+            .travel_times(&[Seed::new(0)], at(8, 8, 0), Duration::hours(1))
+            .unwrap()
+    }
+
+    #[test]
+    fn changes_without_change_time() {
+        let data = transfers(None);
+        let graph = Graph::from_slice(&data).unwrap();
+        let times = travel_times(&graph, SearchOptions::default());
+        assert_eq!(times.get(&3), Some(&Duration::minutes(20)));
+        assert_eq!(times.get(&4), Some(&Duration::minutes(25)));
+    }
+
+    #[test]
+    fn min_change_applies_to_walks() {
+        let data = transfers(None);
+        let graph = Graph::from_slice(&data).unwrap();
+        let options = SearchOptions {
+            min_change: Duration::minutes(5),
+            ..SearchOptions::default()
+        };
+        let times = travel_times(&graph, options);
+        // walking B-C-B in 2 minutes must not avoid the change time
+        assert_eq!(times.get(&3), None);
+        assert_eq!(times.get(&4), Some(&Duration::minutes(25)));
+        // C is reached on foot after the change time
+        assert_eq!(times.get(&2), Some(&Duration::minutes(15)));
+    }
+
+    #[test]
+    fn station_change_time() {
+        let data = transfers(Some(Duration::minutes(5)));
+        let graph = Graph::from_slice(&data).unwrap();
+        let times = travel_times(&graph, SearchOptions::default());
+        assert_eq!(times.get(&3), None);
+        assert_eq!(times.get(&4), Some(&Duration::minutes(25)));
+        let options = SearchOptions {
+            station_change_times: false,
+            ..SearchOptions::default()
+        };
+        assert_eq!(
+            travel_times(&graph, options).get(&3),
+            Some(&Duration::minutes(20))
+        );
+    }
+
+    #[test]
+    fn boarding_penalty_per_vehicle() {
+        let data = transfers(None);
+        let graph = Graph::from_slice(&data).unwrap();
+        let options = SearchOptions {
+            boarding_penalty: Duration::minutes(3),
+            ..SearchOptions::default()
+        };
+        let times = travel_times(&graph, options);
+        assert_eq!(times.get(&1), Some(&Duration::minutes(13)));
+        assert_eq!(times.get(&4), Some(&Duration::minutes(31)));
+    }
+
+    #[test]
+    fn max_transfers_limits_vehicles() {
+        let data = transfers(None);
+        let graph = Graph::from_slice(&data).unwrap();
+        let limited = |max_transfers| SearchOptions {
+            max_transfers: Some(max_transfers),
+            ..SearchOptions::default()
+        };
+        let times = travel_times(&graph, limited(0));
+        assert_eq!(times.get(&1), Some(&Duration::minutes(10)));
+        assert_eq!(times.get(&4), None);
+        let times = travel_times(&graph, limited(1));
+        assert_eq!(times.get(&4), Some(&Duration::minutes(25)));
+    }
+
+    #[test]
+    fn fewer_transfers_survive_faster_labels() {
+        // reaching 2 by changing at 1 is faster than the direct trip,
+        // but only the direct trip leaves a transfer for the trip to 3
+        let data = with_trips(
+            4,
+            &[
+                (1, 0, 1, 480, 485),
+                (2, 1, 2, 486, 490),
+                (3, 0, 2, 480, 500),
+                (4, 2, 3, 505, 510),
+            ],
+        )
+        .to_bytes()
+        .unwrap();
+        let graph = Graph::from_slice(&data).unwrap();
+        let options = SearchOptions {
+            max_transfers: Some(1),
+            ..SearchOptions::default()
+        };
+        let times = travel_times(&graph, options);
+        assert_eq!(times.get(&2), Some(&Duration::minutes(10)));
+        assert_eq!(times.get(&3), Some(&Duration::minutes(30)));
+    }
+
+    /// Returns a graph with two stations and a single journey with trip id.
+    fn single_edge() -> (Vec<u8>, Layout) {
+        let mut builder = GraphBuilder::new();
+        let a = builder.add_node(1, 50.0, 8.0, "A").unwrap();
+        let b = builder.add_node(2, 51.0, 8.0, "B").unwrap();
+        let period = builder.add_period(date(1), date(31), [date(8)]).unwrap();
+        builder
+            .add_journey(a, b, 600, 630, period, Some(7))
+            .unwrap();
+        let data = builder.to_bytes().unwrap();
+        let layout = Layout::of(&data);
+        (data, layout)
+    }
+
+    fn parse_error(data: &[u8]) -> GraphError {
+        Graph::from_slice(data).err().unwrap()
+    }
+
+    #[test]
+    fn valid_edge_is_parsed() {
+        let (data, layout) = single_edge();
+        // the node count precedes the nodes
+        assert_eq!((layout.nodes.len(), layout.nodes[0]), (2, layout.body + 4));
+        assert_eq!(layout.edges.len(), 1);
+        let graph = Graph::from_slice(&data).unwrap();
+        let journey = graph.nodes[0].outgoing[0].journeys().next().unwrap();
+        assert_eq!(journey.trip(), Some(7));
+    }
+
+    #[test]
+    fn truncated_section() {
+        let (data, layout) = single_edge();
+        // the last trip id starts 4 bytes before the end
+        assert_eq!(
+            parse_error(&data[..data.len() - 1]),
+            GraphError::Truncated {
+                offset: layout.section(SECTION_TRIPS) + SECTION_LEN + 4
+            }
+        );
+    }
+
+    #[test]
+    fn edge_to_missing_node() {
+        let (mut data, layout) = single_edge();
+        let edge = &layout.edges[0];
+        data[edge.end..edge.end + 4].copy_from_slice(&2u32.to_le_bytes());
+        assert_eq!(
+            parse_error(&data),
+            GraphError::MissingNode {
+                offset: edge.offset,
+                node: 2
+            }
+        );
+    }
+
+    #[test]
+    fn journey_with_missing_period() {
+        let (mut data, layout) = single_edge();
+        let journey = layout.edges[0].journeys[0];
+        let index = journey + JOURNEY_PERIOD;
+        data[index..index + 2].copy_from_slice(&1u16.to_le_bytes());
+        assert_eq!(
+            parse_error(&data),
+            GraphError::MissingPeriod {
+                offset: journey,
+                index: 1
+            }
+        );
+    }
+
+    #[test]
+    fn period_with_wrong_day_count() {
+        let (mut data, layout) = single_edge();
+        let period = layout.edges[0].periods[0];
+        data[period + PERIOD_DAYS] = 3;
+        assert_eq!(
+            parse_error(&data),
+            GraphError::ValidDays {
+                offset: period,
+                expected: 4,
+                actual: 3
+            }
+        );
+    }
+
+    #[test]
+    fn section_with_wrong_length() {
+        let (mut data, layout) = single_edge();
+        let section = layout.section(SECTION_TRIPS);
+        let len = section + SECTION_LEN;
+        data[len..len + 4].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(
+            parse_error(&data),
+            GraphError::SectionLength {
+                offset: section,
+                tag: SECTION_TRIPS,
+                len: 0
+            }
+        );
     }
 }
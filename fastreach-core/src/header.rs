@@ -0,0 +1,134 @@
+use std::io::Write;
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+
+use crate::error::{BuildError, GraphError, Reader};
+
+/// First bytes of every graph file with header.
+pub const MAGIC: &[u8; 4] = b"FRCH";
+/// Format version written and read by this crate.
+pub const FORMAT_VERSION: u16 = 1;
+/// Journeys carry trip ids.
+pub const FLAG_TRIPS: u16 = 1;
+/// Stations carry minimum change times.
+pub const FLAG_CHANGE_TIMES: u16 = 1 << 1;
+const KNOWN_FLAGS: u16 = FLAG_TRIPS | FLAG_CHANGE_TIMES;
+
+/// Information about a graph file stored in its header.
+///
+/// The header consists of the magic bytes, the version and flags as u16,
+/// the creation time as i64 unix seconds, the first and last day of the timetable
+/// as i32 days since the common era, the time zone name prefixed by its length as u8
+/// and the source description prefixed by its length as u16.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metadata {
+    pub version: u16,
+    pub flags: u16,
+    pub created: DateTime<Utc>,
+    /// first day of the timetable
+    pub valid_from: NaiveDate,
+    /// last day of the timetable
+    pub valid_to: NaiveDate,
+    pub timezone: chrono_tz::Tz,
+    /// describes the data the graph has been generated from
+    pub source: String,
+}
+
+impl Metadata {
+    /// Whether the data starts with the magic bytes.
+    #[must_use]
+    pub fn has_header(data: &[u8]) -> bool {
+        data.starts_with(MAGIC)
+    }
+
+    /// Reads the header of a graph file without parsing the graph.
+    /// # Errors
+    /// If the header is missing, truncated or of an incompatible version.
+    pub fn from_slice(data: &[u8]) -> Result<Self, GraphError> {
+        Self::read(&mut Reader::new(data))
+    }
+
+    pub(crate) fn read(reader: &mut Reader<'_>) -> Result<Self, GraphError> {
+        if !Self::has_header(reader.data()) {
+            return Err(GraphError::MissingMagic);
+        }
+        reader.bytes(MAGIC.len())?;
+        let version = reader.u16()?;
+        if version != FORMAT_VERSION {
+            return Err(GraphError::UnsupportedVersion { version });
+        }
+        let flags = reader.u16()?;
+        if flags & !KNOWN_FLAGS != 0 {
+            return Err(GraphError::UnsupportedFlags { flags });
+        }
+        let offset = reader.offset();
+        let created = DateTime::from_timestamp(reader.i64()?, 0).ok_or(GraphError::Invalid {
+            offset,
+            field: "creation time",
+        })?;
+        let valid_from = Self::read_date(reader, "validity start")?;
+        let offset = reader.offset();
+        let valid_to = Self::read_date(reader, "validity end")?;
+        if valid_from > valid_to {
+            return Err(GraphError::Invalid {
+                offset,
+                field: "validity end",
+            });
+        }
+        let timezone_len = reader.u8()?.into();
+        let timezone = Self::read_str(reader, timezone_len, "time zone")?
+            .parse()
+            .map_err(|_| GraphError::Invalid {
+                offset: reader.offset() - timezone_len,
+                field: "time zone",
+            })?;
+        let source_len = reader.u16()?.into();
+        let source = Self::read_str(reader, source_len, "source")?.to_owned();
+        Ok(Self {
+            version,
+            flags,
+            created,
+            valid_from,
+            valid_to,
+            timezone,
+            source,
+        })
+    }
+
+    /// Writes the header in the format read by `from_slice`.
+    /// # Errors
+    /// If a field exceeds its length limit or writing fails.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), BuildError> {
+        let timezone = self.timezone.name();
+        let timezone_len =
+            u8::try_from(timezone.len()).map_err(|_| BuildError::TooLarge { what: "time zone" })?;
+        let source_len = u16::try_from(self.source.len())
+            .map_err(|_| BuildError::TooLarge { what: "source" })?;
+        writer.write_all(MAGIC)?;
+        writer.write_all(&self.version.to_le_bytes())?;
+        writer.write_all(&self.flags.to_le_bytes())?;
+        writer.write_all(&self.created.timestamp().to_le_bytes())?;
+        writer.write_all(&self.valid_from.num_days_from_ce().to_le_bytes())?;
+        writer.write_all(&self.valid_to.num_days_from_ce().to_le_bytes())?;
+        writer.write_all(&[timezone_len])?;
+        writer.write_all(timezone.as_bytes())?;
+        writer.write_all(&source_len.to_le_bytes())?;
+        writer.write_all(self.source.as_bytes())?;
+        Ok(())
+    }
+
+    fn read_date(reader: &mut Reader<'_>, field: &'static str) -> Result<NaiveDate, GraphError> {
+        let offset = reader.offset();
+        NaiveDate::from_num_days_from_ce_opt(reader.i32()?)
+            .ok_or(GraphError::Invalid { offset, field })
+    }
+
+    fn read_str<'a>(
+        reader: &mut Reader<'a>,
+        len: usize,
+        field: &'static str,
+    ) -> Result<&'a str, GraphError> {
+        let offset = reader.offset();
+        std::str::from_utf8(reader.bytes(len)?).map_err(|_| GraphError::Invalid { offset, field })
+    }
+}
@@ -1,3 +1,15 @@
+pub mod accessibility;
+pub mod batch;
+pub mod builder;
 pub mod cascade;
+pub mod error;
 pub mod graph;
+pub mod header;
+pub mod matrix;
+pub mod profile;
+pub mod route;
 pub mod vincenty;
+pub mod workspace;
+
+#[cfg(test)]
+mod testing;
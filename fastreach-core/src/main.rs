@@ -1,32 +1,731 @@
-use std::fs::File;
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
 
-use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use chrono::{Days, Duration, NaiveDate, NaiveDateTime};
+use clap::{Parser, Subcommand, ValueEnum};
 use fastreach_core::{
-    cascade,
-    graph::{Graph, IsochroneDijsktra},
+    accessibility::{self, Accessibility},
+    batch::{self, Query},
+    builder::GraphBuilder,
+    cascade::{self, Isochrone},
+    graph::{decode_date, Edge, Graph, IsochroneDijsktra, Journey, Node, SearchOptions, Seed},
+    header::Metadata,
+    matrix::Matrix,
+    route::LegMode,
 };
 use geo::{ChamberlainDuquetteArea, Polygon};
 use memmap2::Mmap;
+use rayon::iter::ParallelIterator;
 
 const ERFURT_HBF: u64 = 13_973_471_588_854_917_578;
 
+/// Inspects and benchmarks graph files.
+#[derive(Parser)]
+struct Cli {
+    /// path of the graph
+    #[arg(short, long, env = "FASTREACH_GRAPH", default_value = "graph.bin")]
+    graph: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints counts, validity and the size of each part of the file
+    Stats,
+    /// Lists stations whose name contains the text ignoring case
+    Find { name: String },
+    /// Prints the outgoing edges of a station
+    Dump {
+        id: u64,
+        /// lists the departures on this date instead of all journeys
+        #[arg(short, long)]
+        date: Option<NaiveDate>,
+    },
+    /// Computes the area reachable from a station and writes it as geojson feature
+    Isochrone {
+        /// id or name of the start station
+        station: String,
+        /// departure in the time zone of the graph
+        #[arg(short, long)]
+        start: NaiveDateTime,
+        #[arg(short, long, default_value_t = 60)]
+        minutes: i64,
+        /// path of the written feature, stdout if missing
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Computes the isochrone of every station and ranks them by area
+    Rank {
+        /// departure in the time zone of the graph
+        #[arg(short, long)]
+        start: NaiveDateTime,
+        #[arg(short, long, default_value_t = 60)]
+        minutes: i64,
+        #[arg(short, long, value_enum, default_value_t = Format::Csv)]
+        format: Format,
+        /// path of the written ranking, stdout if missing
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Finds the earliest arrival at a station and the legs leading there
+    Connect {
+        /// id or name of the start station
+        from: String,
+        /// id or name of the target station
+        to: String,
+        /// departure in the time zone of the graph
+        #[arg(short, long)]
+        start: NaiveDateTime,
+        /// give up if the target is further away
+        #[arg(short, long, default_value_t = 480)]
+        minutes: i64,
+        /// speed of the fastest vehicle in km/h, speeds up the search
+        #[arg(long)]
+        max_speed: Option<f32>,
+    },
+    /// Computes the travel times between stations
+    Matrix {
+        /// ids or names of the origins separated by commas
+        #[arg(long, value_delimiter = ',', required = true)]
+        origins: Vec<String>,
+        /// ids or names of the destinations separated by commas, the origins if missing
+        #[arg(long, value_delimiter = ',')]
+        destinations: Vec<String>,
+        /// departure in the time zone of the graph
+        #[arg(short, long)]
+        start: NaiveDateTime,
+        /// destinations further away are unreachable
+        #[arg(short, long, default_value_t = 120)]
+        minutes: i64,
+        #[arg(short, long, value_enum, default_value_t = MatrixFormat::Csv)]
+        format: MatrixFormat,
+        /// path of the written matrix, stdout if missing
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Measures how batches of isochrones scale with the number of threads
+    BenchBatch {
+        #[arg(long, default_value = "2023-10-18T10:15:30")]
+        start: NaiveDateTime,
+        #[arg(long, default_value_t = 60)]
+        minutes: i64,
+        /// number of isochrones from stations spread across the graph
+        #[arg(long, default_value_t = 256)]
+        queries: usize,
+        /// largest number of threads, all cores if missing
+        #[arg(long)]
+        threads: Option<usize>,
+    },
+    /// Measures searches with a new and with a reused search
+    BenchSearch {
+        #[arg(long, default_value = "2024-01-03T08:00:00")]
+        start: NaiveDateTime,
+        #[arg(long, default_value_t = 60)]
+        minutes: i64,
+        /// number of searches from stations spread across the graph
+        #[arg(long, default_value_t = 200)]
+        queries: usize,
+    },
+    /// Writes a grid of stations connected by walks and lines to the graph path for benchmarks
+    Synthetic {
+        /// stations per side of the grid, which are 1 km apart
+        #[arg(long, default_value_t = 100)]
+        size: u32,
+        /// number of lines, which run every 10 minutes in both directions
+        #[arg(long, default_value_t = 100)]
+        lines: u32,
+    },
+    /// Measures the time of an isochrone search
+    Bench {
+        #[arg(long, default_value_t = ERFURT_HBF)]
+        id: u64,
+        #[arg(long, default_value = "2023-10-18T10:15:30")]
+        start: NaiveDateTime,
+        #[arg(long, default_value_t = 180)]
+        minutes: i64,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// one row per station
+    Csv,
+    /// feature collection with a point per station
+    Geojson,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MatrixFormat {
+    /// one row per origin and destination
+    Csv,
+    /// stations and nested arrays of minutes and arrivals per origin
+    Json,
+}
+
 fn main() {
-    let file = File::open("graph.bin").expect("failed to open graph data");
+    let cli = Cli::parse();
+    if let Command::Synthetic { size, lines } = cli.command {
+        synthetic(&cli.graph, size, lines);
+        return;
+    }
+    let file = File::open(&cli.graph).expect("failed to open graph data");
     let mapping = unsafe { Mmap::map(&file).expect("failed mmap") };
-    let graph = Graph::from_slice(&mapping).expect("failed to parse data");
-    let start = std::time::Instant::now();
-    let station_idx = graph.ids.get(&ERFURT_HBF).unwrap();
-    let mut algo = IsochroneDijsktra::new(&graph);
+    let graph = if Metadata::has_header(&mapping) {
+        Graph::from_slice(&mapping)
+    } else {
+        Graph::from_legacy_slice(&mapping)
+    }
+    .expect("failed to parse data");
+    match cli.command {
+        Command::Stats => stats(&graph, mapping.len()),
+        Command::Find { name } => find(&graph, &name),
+        Command::Dump { id, date } => {
+            let Some(idx) = graph.ids.get(&id) else {
+                eprintln!("no station with id {id}");
+                std::process::exit(1);
+            };
+            dump(&graph, &graph.nodes[*idx], date);
+        }
+        Command::Isochrone {
+            station,
+            start,
+            minutes,
+            output,
+        } => isochrone(
+            &graph,
+            station_or_exit(&graph, &station),
+            start,
+            minutes,
+            output,
+        ),
+        Command::Rank {
+            start,
+            minutes,
+            format,
+            output,
+        } => rank(&graph, start, minutes, format, output),
+        Command::Connect {
+            from,
+            to,
+            start,
+            minutes,
+            max_speed,
+        } => {
+            let from = station_or_exit(&graph, &from);
+            let to = station_or_exit(&graph, &to);
+            connect(&graph, from, to, start, minutes, max_speed);
+        }
+        Command::Matrix {
+            origins,
+            destinations,
+            start,
+            minutes,
+            format,
+            output,
+        } => {
+            let resolve = |stations: &[String]| -> Vec<usize> {
+                stations
+                    .iter()
+                    .map(|station| station_or_exit(&graph, station))
+                    .collect()
+            };
+            let origins = resolve(&origins);
+            let destinations = if destinations.is_empty() {
+                origins.clone()
+            } else {
+                resolve(&destinations)
+            };
+            let matrix = Matrix::compute(
+                &graph,
+                SearchOptions::default(),
+                &origins,
+                &destinations,
+                start,
+                Duration::minutes(minutes),
+            )
+            .expect("failed dijsktra");
+            write_matrix(&graph, &matrix, format, output);
+        }
+        Command::BenchBatch {
+            start,
+            minutes,
+            queries,
+            threads,
+        } => bench_batch(&graph, start, minutes, queries, threads),
+        Command::BenchSearch {
+            start,
+            minutes,
+            queries,
+        } => bench_search(&graph, start, minutes, queries),
+        Command::Synthetic { .. } => unreachable!("written before loading the graph"),
+        Command::Bench { id, start, minutes } => bench(&graph, id, start, minutes),
+    }
+}
+
+fn stats(graph: &Graph<'_>, total: usize) {
+    let header = graph.metadata().map_or(0, |metadata| {
+        let mut data = Vec::new();
+        metadata.write(&mut data).expect("failed to size header");
+        data.len()
+    });
+    if let Some(metadata) = graph.metadata() {
+        println!("version:      {}", metadata.version);
+        println!("created:      {}", metadata.created);
+        println!(
+            "valid:        {} to {}",
+            metadata.valid_from, metadata.valid_to
+        );
+        println!("time zone:    {}", metadata.timezone);
+        println!("source:       {}", metadata.source);
+    } else {
+        println!("version:      none, netex-parse output");
+    }
+    let edges: Vec<&Edge<'_>> = graph.nodes.iter().flat_map(|n| &n.outgoing).collect();
+    let journeys: usize = edges.iter().map(|e| e.journeys().count()).sum();
+    let periods: usize = edges.iter().map(|e| e.operating_periods().count()).sum();
+    let walks = edges.iter().filter(|e| e.walk() != u16::MAX).count();
+    println!("nodes:        {}", graph.nodes.len());
+    println!("edges:        {} ({walks} with walks)", edges.len());
+    println!("journeys:     {journeys}");
+    println!("periods:      {periods}");
+    if graph.has_trips() {
+        let trips: HashSet<u32> = edges
+            .iter()
+            .flat_map(|e| e.journeys())
+            .filter_map(|j| j.trip())
+            .collect();
+        println!("trips:        {}", trips.len());
+    }
+    println!("change times: {}", graph.change_times.len());
+
+    let node_bytes: usize = graph.nodes.iter().map(Node::size).sum::<usize>() + 4;
+    let edge_bytes: usize = edges.iter().map(|e| e.size()).sum::<usize>() + 4;
+    let journey_bytes = journeys * Edge::JOURNEY_SIZE;
+    let period_bytes: usize = edges
+        .iter()
+        .flat_map(|e| e.operating_periods())
+        .map(|p| p.size())
+        .sum();
+    println!("size:");
+    println!("  header:     {header} B");
+    println!("  nodes:      {node_bytes} B");
+    println!("  edges:      {edge_bytes} B");
+    println!("    journeys: {journey_bytes} B");
+    println!("    periods:  {period_bytes} B");
+    println!(
+        "  sections:   {} B",
+        total - header - node_bytes - edge_bytes
+    );
+    println!("  total:      {total} B");
+}
+
+fn find(graph: &Graph<'_>, name: &str) {
+    let name = name.to_lowercase();
+    for node in &graph.nodes {
+        let node_name = node.name().unwrap_or_default();
+        if node_name.to_lowercase().contains(&name) {
+            println!(
+                "{}\t{:.5}\t{:.5}\t{node_name}",
+                node.id(),
+                node.lat(),
+                node.lon()
+            );
+        }
+    }
+}
+
+/// Resolves an id or otherwise a name, which matches exactly or as only station partially ignoring case.
+fn find_station(graph: &Graph<'_>, station: &str) -> Result<usize, String> {
+    if let Some(idx) = station.parse().ok().and_then(|id| graph.ids.get(&id)) {
+        return Ok(*idx);
+    }
+    let name = station.to_lowercase();
+    let mut matches = Vec::new();
+    for (idx, node) in graph.nodes.iter().enumerate() {
+        let node_name = node.name().unwrap_or_default().to_lowercase();
+        if node_name == name {
+            return Ok(idx);
+        }
+        if node_name.contains(&name) {
+            matches.push(idx);
+        }
+    }
+    match matches[..] {
+        [idx] => Ok(idx),
+        [] => Err(format!("no station matches {station}")),
+        _ => Err(format!(
+            "{} stations match {station}, use find to pick an id",
+            matches.len()
+        )),
+    }
+}
+
+fn station_or_exit(graph: &Graph<'_>, station: &str) -> usize {
+    find_station(graph, station).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(1);
+    })
+}
+
+fn isochrone(
+    graph: &Graph<'_>,
+    idx: usize,
+    start: NaiveDateTime,
+    minutes: i64,
+    output: Option<PathBuf>,
+) {
+    let mut algo = IsochroneDijsktra::new(graph);
     let reached = algo
-        .nodes_within(
-            *station_idx,
-            NaiveDateTime::new(
-                NaiveDate::from_ymd_opt(2023, 10, 18).unwrap(),
-                NaiveTime::from_hms_opt(10, 15, 30).unwrap(),
-            ),
-            Duration::minutes(180),
+        .nodes_within(idx, start, Duration::minutes(minutes))
+        .expect("failed dijsktra");
+    let isochrone = Isochrone::from_nodes(&reached);
+    let node = &graph.nodes[idx];
+    let mut properties = geojson::JsonObject::new();
+    properties.insert("id".to_owned(), node.id().to_string().into());
+    properties.insert("name".to_owned(), node.name().unwrap_or_default().into());
+    properties.insert("start".to_owned(), start.to_string().into());
+    properties.insert("minutes".to_owned(), minutes.into());
+    properties.insert("area".to_owned(), isochrone.area.into());
+    properties.insert("diameter".to_owned(), isochrone.diameter.into());
+    let feature = geojson::Feature {
+        geometry: Some(geojson::Geometry::from(&isochrone.polygon)),
+        properties: Some(properties),
+        ..geojson::Feature::default()
+    };
+    let mut writer = create(output);
+    writeln!(writer, "{feature}")
+        .and_then(|()| writer.flush())
+        .expect("failed to write isochrone");
+}
+
+fn rank(
+    graph: &Graph<'_>,
+    start: NaiveDateTime,
+    minutes: i64,
+    format: Format,
+    output: Option<PathBuf>,
+) {
+    let ranking = accessibility::rank(
+        graph,
+        SearchOptions::default(),
+        start,
+        Duration::minutes(minutes),
+    )
+    .expect("failed dijsktra");
+    let mut writer = create(output);
+    match format {
+        Format::Csv => write_csv(graph, &ranking, &mut writer).expect("failed to write ranking"),
+        Format::Geojson => {
+            let features = ranking
+                .iter()
+                .enumerate()
+                .map(|(rank, accessibility)| {
+                    let node = &graph.nodes[accessibility.node];
+                    let mut properties = geojson::JsonObject::new();
+                    properties.insert("rank".to_owned(), (rank + 1).into());
+                    properties.insert("id".to_owned(), node.id().to_string().into());
+                    properties.insert("name".to_owned(), node.name().unwrap_or_default().into());
+                    properties.insert("reached".to_owned(), accessibility.reached.into());
+                    properties.insert("area".to_owned(), accessibility.area.into());
+                    properties.insert("diameter".to_owned(), accessibility.diameter.into());
+                    geojson::Feature {
+                        geometry: Some(geojson::Geometry::from(&geo::Point::new(
+                            node.lon(),
+                            node.lat(),
+                        ))),
+                        properties: Some(properties),
+                        ..geojson::Feature::default()
+                    }
+                })
+                .collect();
+            let collection = geojson::FeatureCollection {
+                bbox: None,
+                features,
+                foreign_members: None,
+            };
+            writeln!(writer, "{collection}").expect("failed to write ranking");
+        }
+    }
+    writer.flush().expect("failed to write ranking");
+}
+
+fn write_csv(
+    graph: &Graph<'_>,
+    ranking: &[Accessibility],
+    writer: &mut dyn Write,
+) -> Result<(), csv::Error> {
+    let mut csv = csv::Writer::from_writer(writer);
+    csv.write_record([
+        "rank", "id", "name", "lat", "lon", "reached", "area", "diameter",
+    ])?;
+    for (rank, accessibility) in ranking.iter().enumerate() {
+        let node = &graph.nodes[accessibility.node];
+        csv.write_record([
+            (rank + 1).to_string(),
+            node.id().to_string(),
+            node.name().unwrap_or_default().to_owned(),
+            node.lat().to_string(),
+            node.lon().to_string(),
+            accessibility.reached.to_string(),
+            accessibility.area.to_string(),
+            accessibility.diameter.to_string(),
+        ])?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+fn connect(
+    graph: &Graph<'_>,
+    from: usize,
+    to: usize,
+    start: NaiveDateTime,
+    minutes: i64,
+    max_speed: Option<f32>,
+) {
+    let mut algo = IsochroneDijsktra::new(graph);
+    let connection = algo
+        .connection(
+            &[Seed::new(from)],
+            to,
+            start,
+            Duration::minutes(minutes),
+            max_speed,
         )
         .expect("failed dijsktra");
+    let Some(connection) = connection else {
+        println!("not reachable within {minutes} minutes");
+        return;
+    };
+    println!(
+        "arrival {} after {} min",
+        start + connection.duration,
+        connection.duration.num_minutes()
+    );
+    let name = |idx: usize| graph.nodes[idx].name().unwrap_or_default();
+    for leg in &connection.route.legs {
+        let mode = match leg.mode {
+            LegMode::Walk => "walk".to_owned(),
+            LegMode::Ride(Some(trip)) => format!("trip {trip}"),
+            LegMode::Ride(None) => "ride".to_owned(),
+        };
+        println!(
+            "{} {} -> {} {} by {mode}",
+            leg.departure.format("%H:%M"),
+            name(leg.from),
+            leg.arrival.format("%H:%M"),
+            name(leg.to)
+        );
+        for stop in &leg.stops {
+            println!("        via {}", name(*stop));
+        }
+    }
+}
+
+fn write_matrix(graph: &Graph<'_>, matrix: &Matrix, format: MatrixFormat, output: Option<PathBuf>) {
+    let mut writer = create(output);
+    match format {
+        MatrixFormat::Csv => {
+            write_matrix_csv(graph, matrix, &mut writer).expect("failed to write matrix");
+        }
+        MatrixFormat::Json => {
+            let stations = |nodes: &[usize]| {
+                nodes
+                    .iter()
+                    .map(|idx| {
+                        let node = &graph.nodes[*idx];
+                        let mut station = geojson::JsonObject::new();
+                        station.insert("id".to_owned(), node.id().to_string().into());
+                        station.insert("name".to_owned(), node.name().unwrap_or_default().into());
+                        geojson::JsonValue::from(station)
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let rows = |cell: &dyn Fn(usize, usize) -> geojson::JsonValue| {
+                (0..matrix.origins().len())
+                    .map(|row| {
+                        (0..matrix.destinations().len())
+                            .map(|col| cell(row, col))
+                            .collect::<Vec<_>>()
+                            .into()
+                    })
+                    .collect::<Vec<geojson::JsonValue>>()
+            };
+            // unreachable destinations are null
+            let minutes = rows(&|row, col| {
+                matrix
+                    .travel_time(row, col)
+                    .map(|time| time.num_minutes())
+                    .into()
+            });
+            let arrivals = rows(&|row, col| {
+                matrix
+                    .arrival(row, col)
+                    .map(|arrival| arrival.to_string())
+                    .into()
+            });
+            let mut json = geojson::JsonObject::new();
+            json.insert("origins".to_owned(), stations(matrix.origins()).into());
+            json.insert(
+                "destinations".to_owned(),
+                stations(matrix.destinations()).into(),
+            );
+            json.insert("minutes".to_owned(), minutes.into());
+            json.insert("arrivals".to_owned(), arrivals.into());
+            writeln!(writer, "{}", geojson::JsonValue::from(json)).expect("failed to write matrix");
+        }
+    }
+    writer.flush().expect("failed to write matrix");
+}
+
+fn write_matrix_csv(
+    graph: &Graph<'_>,
+    matrix: &Matrix,
+    writer: &mut dyn Write,
+) -> Result<(), csv::Error> {
+    let mut csv = csv::Writer::from_writer(writer);
+    csv.write_record([
+        "origin",
+        "origin_name",
+        "destination",
+        "destination_name",
+        "minutes",
+        "arrival",
+    ])?;
+    for (row, origin) in matrix.origins().iter().enumerate() {
+        let origin = &graph.nodes[*origin];
+        for (col, destination) in matrix.destinations().iter().enumerate() {
+            let destination = &graph.nodes[*destination];
+            // unreachable destinations have empty times
+            csv.write_record([
+                origin.id().to_string(),
+                origin.name().unwrap_or_default().to_owned(),
+                destination.id().to_string(),
+                destination.name().unwrap_or_default().to_owned(),
+                matrix
+                    .travel_time(row, col)
+                    .map(|time| time.num_minutes().to_string())
+                    .unwrap_or_default(),
+                matrix
+                    .arrival(row, col)
+                    .map(|arrival| arrival.to_string())
+                    .unwrap_or_default(),
+            ])?;
+        }
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+/// Opens the file or stdout if missing.
+fn create(output: Option<PathBuf>) -> Box<dyn Write> {
+    match output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).expect("failed to create output"),
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    }
+}
+
+fn dump(graph: &Graph<'_>, node: &Node<'_>, date: Option<NaiveDate>) {
+    println!(
+        "{} ({}) at {:.5}, {:.5}",
+        node.name().unwrap_or_default(),
+        node.id(),
+        node.lat(),
+        node.lon()
+    );
+    if let Some(idx) = graph.ids.get(&node.id()) {
+        if let Some(change) = u32::try_from(*idx)
+            .ok()
+            .and_then(|idx| graph.change_times.get(&idx))
+        {
+            println!("change time: {} s", change.num_seconds());
+        }
+    }
+    for edge in &node.outgoing {
+        let end = &graph.nodes[edge.end() as usize];
+        let walk = if edge.walk() == u16::MAX {
+            "no walk".to_owned()
+        } else {
+            format!("walk {} s", edge.walk())
+        };
+        println!(
+            "-> {} ({}), {walk}",
+            end.name().unwrap_or_default(),
+            end.id()
+        );
+        if let Some(date) = date {
+            print_departures(edge, date);
+        } else {
+            print_journeys(edge);
+        }
+    }
+}
+
+fn print_departures(edge: &Edge<'_>, date: NaiveDate) {
+    for (journey, departure, arrival) in edge.departures_on(date) {
+        let arrival = if arrival.date() == date {
+            arrival.format("%H:%M").to_string()
+        } else {
+            arrival.format("%Y-%m-%d %H:%M").to_string()
+        };
+        println!(
+            "   {} - {arrival}{}",
+            departure.format("%H:%M"),
+            trip(&journey)
+        );
+    }
+}
+
+fn print_journeys(edge: &Edge<'_>) {
+    for journey in edge.journeys() {
+        println!(
+            "   {} - {} period {}{}",
+            minutes(journey.departure()),
+            minutes(journey.arrival()),
+            journey.operating_period_index(),
+            trip(&journey)
+        );
+    }
+    for (idx, period) in edge.operating_periods().enumerate() {
+        let days: u32 = period.valid_days().iter().map(|b| b.count_ones()).sum();
+        println!(
+            "   period {idx}: {} to {}, {days} days",
+            format_date(period.start()),
+            format_date(period.end())
+        );
+    }
+}
+
+/// Formats minutes of the service day, which may exceed 24:00.
+fn minutes(minutes: u16) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+fn format_date(date: u16) -> String {
+    decode_date(date).map_or_else(|| "invalid".to_owned(), |d| d.to_string())
+}
+
+fn trip(journey: &Journey<'_>) -> String {
+    journey
+        .trip()
+        .map(|trip| format!(" trip {trip}"))
+        .unwrap_or_default()
+}
+
+fn bench(graph: &Graph<'_>, id: u64, start_time: NaiveDateTime, minutes: i64) {
+    let start = std::time::Instant::now();
+    let station_idx = graph.ids.get(&id).unwrap();
+    let mut algo = IsochroneDijsktra::new(graph);
+    let reached = algo
+        .nodes_within(*station_idx, start_time, Duration::minutes(minutes))
+        .expect("failed dijsktra");
     let within_end = std::time::Instant::now();
     println!("within duration: {} ms", (within_end - start).as_millis());
 
@@ -39,3 +738,162 @@ fn main() {
     println!("area: {area} km2");
     println!("diameter: {diameter} km");
 }
+
+#[allow(clippy::cast_precision_loss)]
+fn bench_batch(
+    graph: &Graph<'_>,
+    start: NaiveDateTime,
+    minutes: i64,
+    queries: usize,
+    threads: Option<usize>,
+) {
+    let nodes = graph.nodes.len();
+    let queries: Vec<Query> = (0..queries.min(nodes))
+        .map(|i| Query {
+            node: i * nodes / queries.min(nodes),
+            start,
+            duration: Duration::minutes(minutes),
+        })
+        .collect();
+    let max_threads = threads.unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+    });
+    println!("threads\tduration ms\tqueries/s\tspeedup");
+    let mut single = None;
+    let mut threads = 1;
+    while threads <= max_threads {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("failed to start threads");
+        let start = std::time::Instant::now();
+        let reached: usize = pool.install(|| {
+            batch::isochrones(graph, SearchOptions::default(), &queries)
+                .map(|outcome| outcome.expect("failed dijsktra").reached)
+                .sum()
+        });
+        let elapsed = start.elapsed().as_secs_f64();
+        let single = *single.get_or_insert(elapsed);
+        println!(
+            "{threads}\t{:.0}\t{:.1}\t{:.2}",
+            elapsed * 1000.0,
+            queries.len() as f64 / elapsed,
+            single / elapsed
+        );
+        std::hint::black_box(reached);
+        threads = if threads == max_threads {
+            threads + 1
+        } else {
+            (threads * 2).min(max_threads)
+        };
+    }
+}
+
+/// Stations scattered across the graph by stepping through it with the golden ratio.
+fn spread(graph: &Graph<'_>, count: usize) -> impl Iterator<Item = usize> {
+    let nodes = graph.nodes.len() as u64;
+    (0..count.min(graph.nodes.len()) as u64)
+        .map(move |i| usize::try_from(i * 0x9E37_79B9 % nodes).unwrap_or(0))
+}
+
+fn bench_search(graph: &Graph<'_>, start: NaiveDateTime, minutes: i64, queries: usize) {
+    let duration = Duration::minutes(minutes);
+    let stations: Vec<usize> = spread(graph, queries).collect();
+    let time = std::time::Instant::now();
+    for station in &stations {
+        let reached = IsochroneDijsktra::new(graph)
+            .nodes_within(*station, start, duration)
+            .expect("failed dijsktra");
+        std::hint::black_box(reached);
+    }
+    let new = time.elapsed();
+    let mut algo = IsochroneDijsktra::new(graph);
+    let time = std::time::Instant::now();
+    for station in &stations {
+        let reached = algo
+            .nodes_within(*station, start, duration)
+            .expect("failed dijsktra");
+        std::hint::black_box(reached);
+    }
+    let reused = time.elapsed();
+    let per_query = |elapsed: std::time::Duration| {
+        elapsed.as_secs_f64() * 1000.0 / f64::from(u32::try_from(stations.len()).unwrap_or(1))
+    };
+    println!("new search:    {:.3} ms per query", per_query(new));
+    println!("reused search: {:.3} ms per query", per_query(reused));
+}
+
+/// Multiplier and increment of Knuth's MMIX linear congruential generator.
+const LCG: (u64, u64) = (6_364_136_223_846_793_005, 1_442_695_040_888_963_407);
+
+fn synthetic(path: &PathBuf, size: u32, lines: u32) {
+    let mut state = u64::from(size) << 32 | u64::from(lines);
+    let mut random = |bound: u32| {
+        state = state.wrapping_mul(LCG.0).wrapping_add(LCG.1);
+        u32::try_from((state >> 33) % u64::from(bound)).unwrap_or(0)
+    };
+    let mut builder = GraphBuilder::new().with_source(&format!("synthetic {size}x{size}"));
+    let idx = |x: u32, y: u32| y * size + x;
+    for y in 0..size {
+        for x in 0..size {
+            #[allow(clippy::cast_precision_loss)]
+            let (lat, lon) = (50.0 + y as f32 * 0.009, 10.0 + x as f32 * 0.014);
+            builder
+                .add_node(u64::from(idx(x, y)) + 1, lat, lon, &format!("{x}/{y}"))
+                .expect("failed to add station");
+        }
+    }
+    let walk = Duration::minutes(12);
+    for y in 0..size {
+        for x in 0..size {
+            for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                if nx < size && ny < size {
+                    let (a, b) = (idx(x, y), idx(nx, ny));
+                    builder.add_walk(a, b, walk).expect("failed to add walk");
+                    builder.add_walk(b, a, walk).expect("failed to add walk");
+                }
+            }
+        }
+    }
+    let first = NaiveDate::from_ymd_opt(2024, 1, 1).expect("valid date");
+    let days = first.iter_days().take(7);
+    let period = builder
+        .add_period(first, first + Days::new(6), days)
+        .expect("failed to add period");
+    let mut trip = 0;
+    for _ in 0..lines {
+        let (mut x, mut y) = (random(size), random(size));
+        let (dx, dy) = [(1, 0), (0, 1), (1, 1), (1, -1)][random(4) as usize];
+        let mut stops = vec![idx(x, y)];
+        while stops.len() <= (size / 2) as usize {
+            match (x.checked_add_signed(dx), y.checked_add_signed(dy)) {
+                (Some(nx), Some(ny)) if nx < size && ny < size => (x, y) = (nx, ny),
+                _ => break,
+            }
+            stops.push(idx(x, y));
+        }
+        if stops.len() < 2 {
+            continue;
+        }
+        for departure in (5 * 60..23 * 60).step_by(10) {
+            for forward in [true, false] {
+                let mut time = u16::try_from(departure).expect("valid minutes");
+                let mut add = |a: u32, b: u32| {
+                    builder
+                        .add_journey(a, b, time, time + 2, period, Some(trip))
+                        .expect("failed to add journey");
+                    time += 3;
+                };
+                if forward {
+                    stops.windows(2).for_each(|w| add(w[0], w[1]));
+                } else {
+                    stops.windows(2).rev().for_each(|w| add(w[1], w[0]));
+                }
+                trip += 1;
+            }
+        }
+    }
+    let file = BufWriter::new(File::create(path).expect("failed to create graph"));
+    builder.write(file).expect("failed to write graph");
+    println!("wrote {} stations and {trip} trips", size * size);
+}
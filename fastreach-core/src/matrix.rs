@@ -0,0 +1,121 @@
+use chrono::{Duration, NaiveDateTime};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    graph::{Error, Graph, IsochroneDijsktra, SearchOptions, Seed},
+    workspace::Workspace,
+};
+
+/// Travel times from every origin to every destination departing at the same time.
+pub struct Matrix {
+    origins: Vec<usize>,
+    destinations: Vec<usize>,
+    start: NaiveDateTime,
+    /// row per origin, `None` if the destination is not reached
+    times: Vec<Option<Duration>>,
+}
+
+impl Matrix {
+    /// Runs one search per origin in parallel and keeps the travel times to the destinations.
+    /// Each worker thread reuses the memory of its previous search.
+    /// Destinations further away than `duration` are unreachable.
+    /// # Errors
+    /// If underlying data is invalid.
+    pub fn compute(
+        graph: &Graph<'_>,
+        options: SearchOptions,
+        origins: &[usize],
+        destinations: &[usize],
+        start: NaiveDateTime,
+        duration: Duration,
+    ) -> Result<Self, Error> {
+        let rows = origins
+            .par_iter()
+            .map(|origin| {
+                let algo = IsochroneDijsktra::new(graph).with_options(options);
+                Workspace::pooled(algo, |algo| {
+                    Self::row(algo, *origin, destinations, start, duration)
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Self::from_rows(origins, destinations, start, &rows))
+    }
+
+    /// Runs one search per origin after another on the current thread with the forward
+    /// search, so callers limiting their threads can reuse the memory of their own search.
+    /// # Errors
+    /// If underlying data is invalid.
+    pub fn compute_with(
+        algo: &mut IsochroneDijsktra<'_, '_>,
+        origins: &[usize],
+        destinations: &[usize],
+        start: NaiveDateTime,
+        duration: Duration,
+    ) -> Result<Self, Error> {
+        let rows = origins
+            .iter()
+            .map(|origin| Self::row(algo, *origin, destinations, start, duration))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Self::from_rows(origins, destinations, start, &rows))
+    }
+
+    fn row(
+        algo: &mut IsochroneDijsktra<'_, '_>,
+        origin: usize,
+        destinations: &[usize],
+        start: NaiveDateTime,
+        duration: Duration,
+    ) -> Result<Vec<Option<Duration>>, Error> {
+        let times = algo.travel_times(&[Seed::new(origin)], start, duration)?;
+        Ok(destinations
+            .iter()
+            .map(|destination| {
+                u32::try_from(*destination)
+                    .ok()
+                    .and_then(|idx| times.get(&idx).copied())
+            })
+            .collect())
+    }
+
+    fn from_rows(
+        origins: &[usize],
+        destinations: &[usize],
+        start: NaiveDateTime,
+        rows: &[Vec<Option<Duration>>],
+    ) -> Self {
+        Self {
+            origins: origins.to_vec(),
+            destinations: destinations.to_vec(),
+            start,
+            times: rows.concat(),
+        }
+    }
+
+    #[must_use]
+    pub fn origins(&self) -> &[usize] {
+        &self.origins
+    }
+
+    #[must_use]
+    pub fn destinations(&self) -> &[usize] {
+        &self.destinations
+    }
+
+    /// Travel time from the origin to the destination at the given positions.
+    /// # Panics
+    /// If a position is out of bounds.
+    #[must_use]
+    pub fn travel_time(&self, origin: usize, destination: usize) -> Option<Duration> {
+        assert!(destination < self.destinations.len());
+        self.times[origin * self.destinations.len() + destination]
+    }
+
+    /// Earliest arrival at the destination from the origin at the given positions.
+    /// # Panics
+    /// If a position is out of bounds.
+    #[must_use]
+    pub fn arrival(&self, origin: usize, destination: usize) -> Option<NaiveDateTime> {
+        self.travel_time(origin, destination)
+            .map(|time| self.start + time)
+    }
+}
@@ -0,0 +1,201 @@
+use chrono::{Duration, NaiveDateTime};
+use fnv::{FnvHashMap, FnvHashSet};
+
+use crate::graph::{Error, Graph, IsochroneDijsktra, Seed, TimedNode, Walking};
+
+/// Travel times to a node across all departures of a window.
+pub struct StationProfile {
+    pub node: usize,
+    /// shortest travel time of any departure
+    pub best: Duration,
+    /// `None` if the node is not reached by at least half of the departures
+    pub median: Option<Duration>,
+    /// `None` if the node is not reached by every departure
+    pub worst: Option<Duration>,
+    /// share of departures reaching the node
+    pub share: f32,
+}
+
+/// Reachability for a series of departures within a time window.
+pub struct Profile<'a, 'b> {
+    graph: &'a Graph<'b>,
+    duration: Duration,
+    walking: Walking,
+    departures: usize,
+    /// ascending travel times of the departures reaching a node
+    times: FnvHashMap<u32, Vec<Duration>>,
+    /// nodes contributing to the area of at least one departure
+    candidates: FnvHashSet<u32>,
+}
+
+impl<'a, 'b: 'a> Profile<'a, 'b> {
+    /// Runs a search for every `step` within `[from, to)`.
+    /// # Errors
+    /// If the window is empty, the step is not positive or underlying data is invalid.
+    pub fn compute(
+        algo: &mut IsochroneDijsktra<'a, 'b>,
+        seeds: &[Seed],
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        step: Duration,
+        duration: Duration,
+    ) -> Result<Self, Error> {
+        if step <= Duration::zero() {
+            return Err("step must be positive".into());
+        }
+        if from >= to {
+            return Err("empty departure window".into());
+        }
+        let mut profile = Profile {
+            graph: algo.graph(),
+            duration,
+            walking: algo.options().walking,
+            departures: 0,
+            times: FnvHashMap::default(),
+            candidates: FnvHashSet::default(),
+        };
+        let mut departure = from;
+        while departure < to {
+            let reached = algo.search(seeds, departure, duration)?;
+            profile.candidates.extend(reached.node_indices());
+            for (idx, time) in reached.durations() {
+                profile.times.entry(idx).or_default().push(time);
+            }
+            profile.departures += 1;
+            departure += step;
+        }
+        for times in profile.times.values_mut() {
+            times.sort();
+        }
+        Ok(profile)
+    }
+
+    /// Number of searched departures.
+    #[must_use]
+    pub fn departures(&self) -> usize {
+        self.departures
+    }
+
+    /// Returns the travel time statistics of every node reached by any departure.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn stations(&self) -> Vec<StationProfile> {
+        let median_idx = (self.departures - 1) / 2;
+        self.times
+            .iter()
+            .map(|(idx, times)| StationProfile {
+                node: *idx as usize,
+                best: times[0],
+                median: times.get(median_idx).copied(),
+                worst: if times.len() == self.departures {
+                    times.last().copied()
+                } else {
+                    None
+                },
+                share: times.len() as f32 / self.departures as f32,
+            })
+            .collect()
+    }
+
+    /// Returns the nodes reachable in at least the given share of departures.
+    /// The duration of each node is the time remaining for the slowest of those departures.
+    #[must_use]
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn nodes_reachable_in(&self, share: f32) -> Vec<TimedNode<'a, 'b>> {
+        let required = ((share.clamp(0.0, 1.0) * self.departures as f32).ceil() as usize).max(1);
+        self.candidates
+            .iter()
+            .filter_map(|idx| {
+                let time = self.times.get(idx)?.get(required - 1)?;
+                Some(TimedNode::with_walking(
+                    &self.graph.nodes[*idx as usize],
+                    self.duration - *time,
+                    self.walking,
+                ))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::Profile;
+    use crate::{
+        builder::GraphBuilder,
+        cascade::Isochrone,
+        graph::{Graph, IsochroneDijsktra, Seed},
+        testing::{at, date},
+    };
+
+    /// Two stations connected by trains at 08:10 and 08:40, which take 10 minutes.
+    fn half_hourly() -> Vec<u8> {
+        let mut builder = GraphBuilder::new();
+        let a = builder.add_node(1, 50.0, 8.0, "A").unwrap();
+        let b = builder.add_node(2, 51.0, 8.0, "B").unwrap();
+        let period = builder.add_period(date(1), date(31), [date(8)]).unwrap();
+        builder.add_journey(a, b, 490, 500, period, None).unwrap();
+        builder.add_journey(a, b, 520, 530, period, None).unwrap();
+        builder.to_bytes().unwrap()
+    }
+
+    fn profile<'a, 'b>(graph: &'a Graph<'b>, seeds: &[Seed]) -> Profile<'a, 'b> {
+        let mut algo = IsochroneDijsktra::new(graph);
+        // departures at 08:00, 08:15, 08:30 and 08:45
+        Profile::compute(
+            &mut algo,
+            seeds,
+            at(8, 8, 0),
+            at(8, 9, 0),
+            Duration::minutes(15),
+            Duration::minutes(30),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn statistics_across_departures() {
+        let data = half_hourly();
+        let graph = Graph::from_slice(&data).unwrap();
+        let profile = profile(&graph, &[Seed::new(0)]);
+        assert_eq!(profile.departures(), 4);
+        let stations = profile.stations();
+        let b = stations.iter().find(|station| station.node == 1).unwrap();
+        assert_eq!(b.best, Duration::minutes(20));
+        assert_eq!(b.median, Some(Duration::minutes(20)));
+        assert_eq!(b.worst, None);
+        assert!((b.share - 0.5).abs() < f32::EPSILON);
+
+        let reached = |share| {
+            let mut nodes: Vec<_> = profile
+                .nodes_reachable_in(share)
+                .iter()
+                .map(|node| (node.node.id(), node.duration))
+                .collect();
+            nodes.sort();
+            nodes
+        };
+        assert_eq!(
+            reached(0.5),
+            [(1, Duration::minutes(30)), (2, Duration::minutes(10))]
+        );
+        assert_eq!(reached(0.75), [(1, Duration::minutes(30))]);
+    }
+
+    #[test]
+    fn empty_profile() {
+        let data = half_hourly();
+        let graph = Graph::from_slice(&data).unwrap();
+        let profile = profile(&graph, &[]);
+        let nodes = profile.nodes_reachable_in(1.0);
+        assert!(nodes.is_empty());
+        let isochrone = Isochrone::from_nodes(&nodes);
+        assert!(isochrone.polygon.0.is_empty());
+        assert!(isochrone.area.abs() < f32::EPSILON);
+    }
+}
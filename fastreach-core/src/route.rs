@@ -0,0 +1,167 @@
+use chrono::{Duration, NaiveDateTime};
+use fnv::FnvHashMap;
+
+use crate::graph::Direction;
+
+/// Identifies a label of the search by node, trip and boardings.
+pub(crate) type LabelKey = (u32, Option<u32>, u8);
+
+/// How a label was reached from its predecessor.
+/// Times are elapsed timetable durations in search direction.
+#[derive(Clone, Copy)]
+pub(crate) enum Step {
+    Seed {
+        offset: Duration,
+    },
+    Walk {
+        from: LabelKey,
+        leave: Duration,
+        reach: Duration,
+    },
+    Ride {
+        from: LabelKey,
+        trip: Option<u32>,
+        leave: Duration,
+        reach: Duration,
+    },
+    /// waiting for the change time after leaving a vehicle
+    Change {
+        from: LabelKey,
+    },
+}
+
+/// Predecessors recorded during a search.
+#[derive(Default)]
+pub(crate) struct Trace {
+    pub steps: FnvHashMap<LabelKey, Step>,
+    /// label of the earliest physical arrival at a node
+    pub arrivals: FnvHashMap<u32, LabelKey>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LegMode {
+    Walk,
+    /// riding a vehicle, the trip is `None` if the graph has no trips
+    Ride(Option<u32>),
+}
+
+/// Part of a route using a single mode.
+#[derive(Clone, Debug)]
+pub struct Leg {
+    pub mode: LegMode,
+    pub from: usize,
+    pub to: usize,
+    pub departure: NaiveDateTime,
+    pub arrival: NaiveDateTime,
+    /// nodes passed without leaving the vehicle
+    pub stops: Vec<usize>,
+}
+
+/// Legs in travel order between a seed and a reached node.
+#[derive(Clone, Debug)]
+pub struct Route {
+    /// offset of the seed the route starts or ends at
+    pub offset: Duration,
+    pub legs: Vec<Leg>,
+}
+
+/// Earliest arrival at a single node.
+#[derive(Clone, Debug)]
+pub struct Connection {
+    /// travel time including the offset of the seed
+    pub duration: Duration,
+    pub route: Route,
+}
+
+/// Reconstructs the routes to all nodes reached by a search.
+pub struct Routes {
+    start: NaiveDateTime,
+    direction: Direction,
+    trace: Trace,
+}
+
+impl Routes {
+    pub(crate) fn new(start: NaiveDateTime, direction: Direction, trace: Trace) -> Self {
+        Self {
+            start,
+            direction,
+            trace,
+        }
+    }
+
+    /// Whether the node has been reached.
+    #[must_use]
+    pub fn contains(&self, node: usize) -> bool {
+        u32::try_from(node).is_ok_and(|idx| self.trace.arrivals.contains_key(&idx))
+    }
+
+    /// Returns the route of the earliest arrival at the node.
+    /// With `Direction::Reverse` the route starts at the node and ends at a seed.
+    #[must_use]
+    pub fn route(&self, node: usize) -> Option<Route> {
+        let mut key = *self.trace.arrivals.get(&u32::try_from(node).ok()?)?;
+        let mut legs = Vec::new();
+        let offset = loop {
+            let (from, mode, leave, reach) = match *self.trace.steps.get(&key)? {
+                Step::Seed { offset } => break offset,
+                Step::Change { from } => {
+                    key = from;
+                    continue;
+                }
+                Step::Walk { from, leave, reach } => (from, LegMode::Walk, leave, reach),
+                Step::Ride {
+                    from,
+                    trip,
+                    leave,
+                    reach,
+                } => (from, LegMode::Ride(trip), leave, reach),
+            };
+            legs.push(match self.direction {
+                Direction::Forward => Leg {
+                    mode,
+                    from: from.0 as usize,
+                    to: key.0 as usize,
+                    departure: self.start + leave,
+                    arrival: self.start + reach,
+                    stops: Vec::new(),
+                },
+                Direction::Reverse => Leg {
+                    mode,
+                    from: key.0 as usize,
+                    to: from.0 as usize,
+                    departure: self.start - reach,
+                    arrival: self.start - leave,
+                    stops: Vec::new(),
+                },
+            });
+            key = from;
+        };
+        if self.direction == Direction::Forward {
+            legs.reverse();
+        }
+        Some(Route {
+            offset,
+            legs: Self::merge_rides(legs),
+        })
+    }
+
+    /// Joins consecutive rides of the same trip into a single leg.
+    fn merge_rides(legs: Vec<Leg>) -> Vec<Leg> {
+        let mut merged: Vec<Leg> = Vec::with_capacity(legs.len());
+        for leg in legs {
+            if let Some(last) = merged.last_mut() {
+                if let (LegMode::Ride(Some(a)), LegMode::Ride(Some(b))) = (last.mode, leg.mode) {
+                    if a == b && last.to == leg.from {
+                        last.stops.push(last.to);
+                        last.stops.extend(leg.stops);
+                        last.to = leg.to;
+                        last.arrival = leg.arrival;
+                        continue;
+                    }
+                }
+            }
+            merged.push(leg);
+        }
+        merged
+    }
+}
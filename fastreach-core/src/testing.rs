@@ -0,0 +1,111 @@
+//! Helpers shared by the unit tests.
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::{error::Reader, graph::Edge, header::Metadata};
+
+/// Offset of the operating period index within a journey.
+pub const JOURNEY_PERIOD: usize = 3;
+/// Offset of the number of valid day bytes within an operating period.
+pub const PERIOD_DAYS: usize = 4;
+/// Offset of the length within a section.
+pub const SECTION_LEN: usize = 1;
+
+/// Day of January 2024, whose 8th is a Monday.
+pub fn date(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 1, day).unwrap()
+}
+
+pub fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    date(day).and_hms_opt(hour, minute, 0).unwrap()
+}
+
+/// Offsets of the records of a graph file, found by reading the lengths stored in it.
+pub struct Layout {
+    /// start of the node count, 0 for legacy graphs
+    pub body: usize,
+    pub nodes: Vec<usize>,
+    pub edges: Vec<EdgeLayout>,
+    /// tag and offset of each trailing section
+    pub sections: Vec<(u8, usize)>,
+}
+
+pub struct EdgeLayout {
+    pub offset: usize,
+    /// offset of the end node
+    pub end: usize,
+    pub journeys: Vec<usize>,
+    pub periods: Vec<usize>,
+}
+
+impl Layout {
+    /// Walks the records of a valid graph with or without header.
+    pub fn of(data: &[u8]) -> Self {
+        let mut reader = Reader::new(data);
+        if Metadata::has_header(data) {
+            Metadata::read(&mut reader).unwrap();
+        }
+        let body = reader.offset();
+        let nodes = (0..reader.u32().unwrap())
+            .map(|_| {
+                let offset = reader.offset();
+                reader.bytes(16).unwrap();
+                let name = reader.u16().unwrap();
+                reader.bytes(name.into()).unwrap();
+                offset
+            })
+            .collect();
+        let edges = (0..reader.u32().unwrap())
+            .map(|_| {
+                let offset = reader.offset();
+                reader.u32().unwrap();
+                let end = reader.offset();
+                reader.bytes(6).unwrap();
+                let journeys = (0..reader.u16().unwrap())
+                    .map(|_| {
+                        let journey = reader.offset();
+                        reader.bytes(Edge::JOURNEY_SIZE).unwrap();
+                        journey
+                    })
+                    .collect();
+                let end_of_periods = usize::from(reader.u16().unwrap()) + reader.offset();
+                let mut periods = Vec::new();
+                while reader.offset() < end_of_periods {
+                    periods.push(reader.offset());
+                    reader.bytes(PERIOD_DAYS).unwrap();
+                    let days = reader.u8().unwrap();
+                    reader.bytes(days.into()).unwrap();
+                }
+                EdgeLayout {
+                    offset,
+                    end,
+                    journeys,
+                    periods,
+                }
+            })
+            .collect();
+        let mut sections = Vec::new();
+        while !reader.is_empty() {
+            let offset = reader.offset();
+            let tag = reader.u8().unwrap();
+            let len = reader.u32().unwrap();
+            reader.bytes(len as usize).unwrap();
+            sections.push((tag, offset));
+        }
+        Self {
+            body,
+            nodes,
+            edges,
+            sections,
+        }
+    }
+
+    /// Offset of the first section with the tag.
+    pub fn section(&self, tag: u8) -> usize {
+        self.sections
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, offset)| *offset)
+            .unwrap()
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    cell::Cell,
+    cmp::Reverse,
+    collections::BinaryHeap,
+    ops::{Deref, DerefMut},
+};
+
+use chrono::Duration;
+use fnv::FnvHashMap;
+
+use crate::{
+    graph::{IsochroneDijsktra, Queued},
+    route::LabelKey,
+};
+
+thread_local! {
+    /// Memory of the last pooled search on this thread.
+    static POOL: Cell<Workspace> = Cell::new(Workspace::new());
+}
+
+/// Search data of a node, which is only valid if the generation matches the current search.
+#[derive(Clone, Copy)]
+struct Slot {
+    generation: u32,
+    /// earliest physical arrival
+    arrival: Duration,
+    /// remaining duration, if the circle of the node is not covered by its predecessor
+    circle: Duration,
+    /// best label without trip and boardings
+    label: Duration,
+}
+
+impl Slot {
+    /// Marks unset values.
+    const UNSET: Duration = Duration::MAX;
+
+    fn new(generation: u32) -> Self {
+        Self {
+            generation,
+            arrival: Self::UNSET,
+            circle: Self::UNSET,
+            label: Self::UNSET,
+        }
+    }
+}
+
+fn set(value: Duration) -> Option<Duration> {
+    (value != Slot::UNSET).then_some(value)
+}
+
+/// Memory of a search, which is kept by `IsochroneDijsktra` and reused by its next search.
+/// Node data is stored in dense arrays, which are reset in constant time by starting a
+/// new generation instead of clearing them.
+#[derive(Default)]
+pub struct Workspace {
+    generation: u32,
+    slots: Vec<Slot>,
+    /// nodes touched by the current search
+    touched: Vec<u32>,
+    /// best labels on board or with counted boardings, which only searches modeling transfers
+    /// create, but then for most reached nodes and with one per trip and number of boardings
+    labels: FnvHashMap<LabelKey, Duration>,
+    heap: BinaryHeap<Reverse<Queued>>,
+}
+
+impl Workspace {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `f` with the search using the workspace of the current thread,
+    /// so parallel searches allocate once per worker thread instead of once per job.
+    pub(crate) fn pooled<'a, 'b: 'a, R>(
+        algo: IsochroneDijsktra<'a, 'b>,
+        f: impl FnOnce(&mut IsochroneDijsktra<'a, 'b>) -> R,
+    ) -> R {
+        f(&mut PooledSearch::new(algo))
+    }
+
+    /// Forgets the previous search and prepares for a graph with `nodes` nodes.
+    pub(crate) fn reset(&mut self, nodes: usize) {
+        self.generation = self.generation.wrapping_add(1);
+        if self.generation == 0 {
+            // slots of a search 2^32 searches ago would appear valid again
+            self.slots.fill(Slot::new(0));
+            self.generation = 1;
+        }
+        self.slots.resize(nodes, Slot::new(0));
+        self.touched.clear();
+        self.labels.clear();
+        self.heap.clear();
+    }
+
+    fn slot(&self, node: u32) -> Option<&Slot> {
+        let slot = &self.slots[node as usize];
+        (slot.generation == self.generation).then_some(slot)
+    }
+
+    fn slot_mut(&mut self, node: u32) -> &mut Slot {
+        let slot = &mut self.slots[node as usize];
+        if slot.generation != self.generation {
+            *slot = Slot::new(self.generation);
+            self.touched.push(node);
+        }
+        slot
+    }
+
+    pub(crate) fn arrival(&self, node: u32) -> Option<Duration> {
+        self.slot(node).and_then(|slot| set(slot.arrival))
+    }
+
+    pub(crate) fn set_arrival(&mut self, node: u32, elapsed: Duration) {
+        self.slot_mut(node).arrival = elapsed;
+    }
+
+    pub(crate) fn set_circle(&mut self, node: u32, remaining: Duration) {
+        self.slot_mut(node).circle = remaining;
+    }
+
+    pub(crate) fn label(&self, key: LabelKey) -> Option<Duration> {
+        match key {
+            (node, None, 0) => self.slot(node).and_then(|slot| set(slot.label)),
+            _ => self.labels.get(&key).copied(),
+        }
+    }
+
+    pub(crate) fn set_label(&mut self, key: LabelKey, elapsed: Duration) {
+        match key {
+            (node, None, 0) => self.slot_mut(node).label = elapsed,
+            _ => {
+                self.labels.insert(key, elapsed);
+            }
+        }
+    }
+
+    pub(crate) fn push(&mut self, queued: Queued) {
+        self.heap.push(Reverse(queued));
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Queued> {
+        self.heap.pop().map(|Reverse(queued)| queued)
+    }
+
+    /// Earliest arrival at every reached node.
+    pub(crate) fn arrivals(&self) -> impl Iterator<Item = (u32, Duration)> + '_ {
+        self.touched
+            .iter()
+            .filter_map(|node| Some((*node, self.arrival(*node)?)))
+    }
+
+    /// Remaining duration of every node whose circle is part of the isochrone.
+    pub(crate) fn circles(&self) -> impl Iterator<Item = (u32, Duration)> + '_ {
+        self.touched.iter().filter_map(|node| {
+            let slot = self.slot(*node)?;
+            Some((*node, set(slot.circle)?))
+        })
+    }
+}
+
+/// Search borrowing the workspace of the current thread until it is dropped,
+/// so searches on long-lived threads, like those of a server, allocate once per thread.
+pub struct PooledSearch<'a, 'b: 'a>(IsochroneDijsktra<'a, 'b>);
+
+impl<'a, 'b: 'a> PooledSearch<'a, 'b> {
+    #[must_use]
+    pub fn new(algo: IsochroneDijsktra<'a, 'b>) -> Self {
+        Self(algo.with_workspace(POOL.take()))
+    }
+}
+
+impl<'a, 'b: 'a> Deref for PooledSearch<'a, 'b> {
+    type Target = IsochroneDijsktra<'a, 'b>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for PooledSearch<'_, '_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Drop for PooledSearch<'_, '_> {
+    fn drop(&mut self) {
+        POOL.set(self.0.take_workspace());
+    }
+}
@@ -0,0 +1,38 @@
+use fastreach_core::error::{BuildError, GraphError};
+use thiserror::Error;
+
+/// Reasons for failing to import a timetable.
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+    #[error(transparent)]
+    Xml(#[from] quick_xml::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("{file}: {source}")]
+    Csv {
+        file: &'static str,
+        source: csv::Error,
+    },
+    #[error("missing {file}")]
+    MissingFile { file: &'static str },
+    #[error("{file}: invalid {field} {value:?}")]
+    Invalid {
+        file: &'static str,
+        field: &'static str,
+        value: String,
+    },
+    #[error("{file}: unknown {field} {value:?}")]
+    Unknown {
+        file: &'static str,
+        field: &'static str,
+        value: String,
+    },
+    #[error(transparent)]
+    Build(#[from] BuildError),
+    #[error(transparent)]
+    Graph(#[from] GraphError),
+}
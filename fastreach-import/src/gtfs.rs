@@ -0,0 +1,501 @@
+use std::{
+    collections::BTreeSet,
+    io::{Read, Seek},
+};
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use fastreach_core::{
+    builder::{GraphBuilder, PeriodId},
+    error::BuildError,
+    graph::Walking,
+};
+use fnv::FnvHashMap;
+use geo::{Distance, Haversine};
+use serde::de::DeserializeOwned;
+use zip::{result::ZipError, ZipArchive};
+
+use crate::{
+    error::ImportError,
+    timetable::{self, StopEvent, MAX_PERIOD_DAYS},
+};
+
+const AGENCY: &str = "agency.txt";
+const STOPS: &str = "stops.txt";
+const TRIPS: &str = "trips.txt";
+const STOP_TIMES: &str = "stop_times.txt";
+const CALENDAR: &str = "calendar.txt";
+const CALENDAR_DATES: &str = "calendar_dates.txt";
+const TRANSFERS: &str = "transfers.txt";
+
+/// Boarding areas belong to platforms, which belong to stations.
+const MAX_PARENT_DEPTH: usize = 3;
+/// Transfers of this type and above are impossible or do not leave the vehicle.
+const TRANSFER_IMPOSSIBLE: u8 = 3;
+
+#[derive(serde_derive::Deserialize)]
+struct Agency {
+    agency_timezone: String,
+}
+
+// fields are named after the columns of the files
+#[allow(clippy::struct_field_names)]
+#[derive(serde_derive::Deserialize)]
+struct Stop {
+    stop_id: String,
+    stop_name: Option<String>,
+    stop_lat: Option<f32>,
+    stop_lon: Option<f32>,
+    parent_station: Option<String>,
+}
+
+#[derive(serde_derive::Deserialize)]
+struct Calendar {
+    service_id: String,
+    monday: u8,
+    tuesday: u8,
+    wednesday: u8,
+    thursday: u8,
+    friday: u8,
+    saturday: u8,
+    sunday: u8,
+    start_date: String,
+    end_date: String,
+}
+
+impl Calendar {
+    fn runs_on(&self, weekday: Weekday) -> bool {
+        let flag = match weekday {
+            Weekday::Mon => self.monday,
+            Weekday::Tue => self.tuesday,
+            Weekday::Wed => self.wednesday,
+            Weekday::Thu => self.thursday,
+            Weekday::Fri => self.friday,
+            Weekday::Sat => self.saturday,
+            Weekday::Sun => self.sunday,
+        };
+        flag == 1
+    }
+}
+
+#[derive(serde_derive::Deserialize)]
+struct CalendarDate {
+    service_id: String,
+    date: String,
+    exception_type: u8,
+}
+
+#[derive(serde_derive::Deserialize)]
+struct Trip {
+    trip_id: String,
+    service_id: String,
+}
+
+#[derive(serde_derive::Deserialize)]
+struct StopTime {
+    trip_id: String,
+    arrival_time: Option<String>,
+    departure_time: Option<String>,
+    stop_id: String,
+    stop_sequence: u32,
+}
+
+#[allow(clippy::struct_field_names)]
+#[derive(serde_derive::Deserialize)]
+struct Transfer {
+    from_stop_id: Option<String>,
+    to_stop_id: Option<String>,
+    transfer_type: Option<u8>,
+    min_transfer_time: Option<u32>,
+}
+
+/// Converts a zipped GTFS feed into a graph.
+///
+/// Stops are merged into their parent station, consecutive stops of a trip become
+/// journeys, calendars become operating periods and transfers become walks or, within
+/// a station, change times. Calendars are limited to the first 2040 days.
+/// Trips with decreasing times are skipped and their number is returned with the graph.
+/// # Errors
+/// If a required file is missing or the feed is inconsistent.
+pub fn import<R: Read + Seek>(reader: R) -> Result<(GraphBuilder, usize), ImportError> {
+    let mut archive = ZipArchive::new(reader)?;
+    let mut builder = GraphBuilder::new();
+    if let Some(timezone) = read_timezone(&mut archive)? {
+        builder = builder.with_timezone(timezone);
+    }
+    let stations = read_stops(&mut archive, &mut builder)?;
+    let periods = read_services(&mut archive, &mut builder)?;
+    let skipped = read_trips(&mut archive, &mut builder, &stations, &periods)?;
+    read_transfers(&mut archive, &mut builder, &stations)?;
+    Ok((builder, skipped))
+}
+
+/// Calls `f` for every record of the file. Returns false if an optional file is missing.
+fn read_records<R: Read + Seek, T: DeserializeOwned>(
+    archive: &mut ZipArchive<R>,
+    file: &'static str,
+    required: bool,
+    mut f: impl FnMut(T) -> Result<(), ImportError>,
+) -> Result<bool, ImportError> {
+    let entry = match archive.by_name(file) {
+        Ok(entry) => entry,
+        Err(ZipError::FileNotFound) if !required => return Ok(false),
+        Err(ZipError::FileNotFound) => return Err(ImportError::MissingFile { file }),
+        Err(err) => return Err(err.into()),
+    };
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(entry);
+    for record in reader.deserialize() {
+        f(record.map_err(|source| ImportError::Csv { file, source })?)?;
+    }
+    Ok(true)
+}
+
+/// Time zone of the first agency, which applies to all times of the feed.
+fn read_timezone<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Result<Option<chrono_tz::Tz>, ImportError> {
+    let mut timezone = None;
+    read_records(archive, AGENCY, false, |agency: Agency| {
+        if timezone.is_none() {
+            let parsed = agency
+                .agency_timezone
+                .parse()
+                .map_err(|_| ImportError::Invalid {
+                    file: AGENCY,
+                    field: "agency_timezone",
+                    value: agency.agency_timezone.clone(),
+                })?;
+            timezone = Some(parsed);
+        }
+        Ok(())
+    })?;
+    Ok(timezone)
+}
+
+/// Adds a node for every stop without parent station and maps all stop ids to their node.
+fn read_stops<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    builder: &mut GraphBuilder,
+) -> Result<FnvHashMap<String, u32>, ImportError> {
+    let mut stops = FnvHashMap::default();
+    // nodes are added in the order of the file
+    let mut order = Vec::new();
+    read_records(archive, STOPS, true, |stop: Stop| {
+        order.push(stop.stop_id.clone());
+        stops.insert(stop.stop_id.clone(), stop);
+        Ok(())
+    })?;
+    let mut stations = FnvHashMap::default();
+    for id in order {
+        let mut root = &stops[&id];
+        for _ in 0..MAX_PARENT_DEPTH {
+            match root.parent_station.as_ref().and_then(|p| stops.get(p)) {
+                Some(parent) => root = parent,
+                None => break,
+            }
+        }
+        let node = if let Some(node) = stations.get(&root.stop_id) {
+            *node
+        } else {
+            let node = add_station(builder, root)?;
+            stations.insert(root.stop_id.clone(), node);
+            node
+        };
+        stations.insert(id, node);
+    }
+    Ok(stations)
+}
+
+fn add_station(builder: &mut GraphBuilder, stop: &Stop) -> Result<u32, ImportError> {
+    let (Some(lat), Some(lon)) = (stop.stop_lat, stop.stop_lon) else {
+        return Err(ImportError::Invalid {
+            file: STOPS,
+            field: "location of stop",
+            value: stop.stop_id.clone(),
+        });
+    };
+    let name = stop.stop_name.as_deref().unwrap_or_default();
+    Ok(builder.add_node(timetable::node_id(&stop.stop_id), lat, lon, name)?)
+}
+
+/// Adds an operating period for every service with at least one day of operation.
+fn read_services<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    builder: &mut GraphBuilder,
+) -> Result<FnvHashMap<String, PeriodId>, ImportError> {
+    let mut services = FnvHashMap::<String, BTreeSet<NaiveDate>>::default();
+    let has_calendar = read_records(archive, CALENDAR, false, |calendar: Calendar| {
+        let start = parse_date(CALENDAR, &calendar.start_date)?;
+        let end = parse_date(CALENDAR, &calendar.end_date)?
+            .min(start + Duration::days(MAX_PERIOD_DAYS - 1));
+        let days = services.entry(calendar.service_id.clone()).or_default();
+        days.extend(
+            start
+                .iter_days()
+                .take_while(|day| *day <= end)
+                .filter(|day| calendar.runs_on(day.weekday())),
+        );
+        Ok(())
+    })?;
+    let has_dates = read_records(archive, CALENDAR_DATES, false, |date: CalendarDate| {
+        let day = parse_date(CALENDAR_DATES, &date.date)?;
+        let days = services.entry(date.service_id).or_default();
+        match date.exception_type {
+            1 => days.insert(day),
+            2 => days.remove(&day),
+            other => {
+                return Err(ImportError::Invalid {
+                    file: CALENDAR_DATES,
+                    field: "exception_type",
+                    value: other.to_string(),
+                })
+            }
+        };
+        Ok(())
+    })?;
+    if !has_calendar && !has_dates {
+        return Err(ImportError::MissingFile { file: CALENDAR });
+    }
+    let mut periods = FnvHashMap::default();
+    for (service, days) in services {
+        if let Some(period) = timetable::add_period(builder, &days)? {
+            periods.insert(service, period);
+        }
+    }
+    Ok(periods)
+}
+
+fn parse_date(file: &'static str, value: &str) -> Result<NaiveDate, ImportError> {
+    NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| ImportError::Invalid {
+        file,
+        field: "date",
+        value: value.to_owned(),
+    })
+}
+
+/// Parses HH:MM:SS relative to the service day into minutes, dropping the seconds.
+fn parse_time(value: &str) -> Result<u16, ImportError> {
+    let invalid = || ImportError::Invalid {
+        file: STOP_TIMES,
+        field: "time",
+        value: value.to_owned(),
+    };
+    let mut parts = value.split(':').map(str::parse::<u16>);
+    let (Some(Ok(hours)), Some(Ok(minutes)), Some(Ok(_)), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    hours
+        .checked_mul(60)
+        .and_then(|hours| hours.checked_add(minutes))
+        .filter(|_| minutes < 60)
+        .ok_or_else(invalid)
+}
+
+/// Adds the journeys of every trip operating on at least one day.
+/// Returns the number of trips skipped, because their times decrease.
+fn read_trips<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    builder: &mut GraphBuilder,
+    stations: &FnvHashMap<String, u32>,
+    periods: &FnvHashMap<String, PeriodId>,
+) -> Result<usize, ImportError> {
+    let mut indices = FnvHashMap::default();
+    let mut trips = Vec::new();
+    read_records(archive, TRIPS, true, |trip: Trip| {
+        // trips without days of operation are never used
+        if let Some(period) = periods.get(&trip.service_id) {
+            indices.insert(trip.trip_id.clone(), trips.len());
+            trips.push((trip.trip_id, *period));
+        }
+        Ok(())
+    })?;
+    let mut events: Vec<Vec<(u32, StopEvent)>> = trips.iter().map(|_| Vec::new()).collect();
+    read_records(archive, STOP_TIMES, true, |time: StopTime| {
+        let Some(trip) = indices.get(&time.trip_id) else {
+            return Ok(());
+        };
+        let node = *stations
+            .get(&time.stop_id)
+            .ok_or_else(|| ImportError::Unknown {
+                file: STOP_TIMES,
+                field: "stop_id",
+                value: time.stop_id.clone(),
+            })?;
+        let event = StopEvent {
+            node,
+            arrival: time.arrival_time.as_deref().map(parse_time).transpose()?,
+            departure: time.departure_time.as_deref().map(parse_time).transpose()?,
+        };
+        events[*trip].push((time.stop_sequence, event));
+        Ok(())
+    })?;
+    let mut skipped = 0;
+    for (idx, ((_, period), mut events)) in trips.into_iter().zip(events).enumerate() {
+        let trip = u32::try_from(idx).map_err(|_| BuildError::TooLarge { what: "trips" })?;
+        events.sort_unstable_by_key(|(sequence, _)| *sequence);
+        let events: Vec<_> = events.into_iter().map(|(_, event)| event).collect();
+        // national feeds contain a few broken trips, which must not prevent the import
+        if !timetable::add_trip(builder, &events, period, trip)? {
+            skipped += 1;
+        }
+    }
+    Ok(skipped)
+}
+
+/// Adds walks between stations and change times within stations.
+/// Walks without minimum transfer time take as long as the direct distance.
+fn read_transfers<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    builder: &mut GraphBuilder,
+    stations: &FnvHashMap<String, u32>,
+) -> Result<(), ImportError> {
+    let station = |id: &str| {
+        stations
+            .get(id)
+            .copied()
+            .ok_or_else(|| ImportError::Unknown {
+                file: TRANSFERS,
+                field: "stop_id",
+                value: id.to_owned(),
+            })
+    };
+    let mut change_times = FnvHashMap::<u32, u32>::default();
+    read_records(archive, TRANSFERS, false, |transfer: Transfer| {
+        // transfers between trips or routes may not name stops
+        let (Some(from), Some(to)) = (transfer.from_stop_id, transfer.to_stop_id) else {
+            return Ok(());
+        };
+        if transfer.transfer_type.unwrap_or_default() >= TRANSFER_IMPOSSIBLE {
+            return Ok(());
+        }
+        let (from, to) = (station(&from)?, station(&to)?);
+        if from == to {
+            if let Some(seconds) = transfer.min_transfer_time {
+                let change = change_times.entry(from).or_default();
+                *change = (*change).max(seconds);
+            }
+            return Ok(());
+        }
+        let duration = if let Some(seconds) = transfer.min_transfer_time {
+            Duration::seconds(seconds.into())
+        } else {
+            let (Some(start), Some(end)) = (builder.point(from), builder.point(to)) else {
+                return Ok(());
+            };
+            Walking::default().duration(Haversine.distance(start, end))
+        };
+        builder.add_walk(from, to, duration)?;
+        Ok(())
+    })?;
+    for (node, seconds) in change_times {
+        builder.set_change_time(node, Duration::seconds(seconds.into()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use chrono::Duration;
+    use fastreach_core::graph::Graph;
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    use super::import;
+    use crate::testing::{date, edge, node};
+
+    const FEED: [(&str, &str); 7] = [
+        (
+            "agency.txt",
+            "agency_id,agency_name,agency_url,agency_timezone\n\
+             A,Agency,https://example.com,Europe/Berlin\n",
+        ),
+        (
+            "stops.txt",
+            "stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station\n\
+             S1,First,52.5,13.4,1,\n\
+             P1,First 1,52.5,13.4,0,S1\n\
+             S2,Second,52.6,13.5,,\n\
+             S3,Third,52.601,13.501,,\n",
+        ),
+        (
+            "calendar.txt",
+            "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\n\
+             WD,1,1,1,1,1,0,0,20240101,20240131\n",
+        ),
+        (
+            "calendar_dates.txt",
+            "service_id,date,exception_type\n\
+             WD,20240108,2\n\
+             X,20240113,1\n",
+        ),
+        (
+            "trips.txt",
+            "route_id,service_id,trip_id\n\
+             R,WD,T1\n\
+             R,X,T2\n\
+             R,WD,BAD\n",
+        ),
+        (
+            "stop_times.txt",
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+             T1,08:00:00,08:00:00,P1,1\n\
+             T1,08:10:00,08:10:00,S2,2\n\
+             T2,23:50:00,23:50:00,S2,1\n\
+             T2,24:20:00,24:20:00,S1,2\n\
+             BAD,10:00:00,10:00:00,S1,1\n\
+             BAD,09:50:00,09:50:00,S2,2\n",
+        ),
+        (
+            "transfers.txt",
+            "from_stop_id,to_stop_id,transfer_type,min_transfer_time\n\
+             S2,S3,2,120\n\
+             P1,S1,2,300\n",
+        ),
+    ];
+
+    fn feed() -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, content) in FEED {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn minimal_feed() {
+        let (builder, skipped) = import(feed()).unwrap();
+        assert_eq!(skipped, 1);
+        let data = builder.to_bytes().unwrap();
+        let graph = Graph::from_slice(&data).unwrap();
+        assert_eq!(graph.timezone(), chrono_tz::Europe::Berlin);
+        // the platform is merged into its station
+        assert_eq!(graph.nodes.len(), 3);
+        assert!(!graph.ids.contains_key(&crate::timetable::node_id("P1")));
+
+        // removed by calendar_dates and the skipped trip adds nothing
+        let first = edge(&graph, "S1", "S2");
+        assert!(first.departures_on(date(8)).is_empty());
+        let departures = first.departures_on(date(9));
+        assert_eq!(departures.len(), 1);
+        assert_eq!(departures[0].1, date(9).and_hms_opt(8, 0, 0).unwrap());
+
+        // added by calendar_dates and arriving after midnight
+        let late = edge(&graph, "S2", "S1");
+        let journey = late.journeys().next().unwrap();
+        assert_eq!((journey.departure(), journey.arrival()), (1430, 1460));
+        let departures = late.departures_on(date(13));
+        assert_eq!(departures.len(), 1);
+        assert_eq!(departures[0].2, date(14).and_hms_opt(0, 20, 0).unwrap());
+
+        // transfers between stations are walks, within a station change times
+        assert_eq!(edge(&graph, "S2", "S3").walk(), 120);
+        let station = u32::try_from(node(&graph, "S1")).unwrap();
+        assert_eq!(graph.change_times[&station], Duration::seconds(300));
+    }
+}
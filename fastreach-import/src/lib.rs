@@ -0,0 +1,8 @@
+pub mod error;
+pub mod gtfs;
+pub mod netex;
+pub mod stations;
+#[cfg(test)]
+mod testing;
+mod timetable;
+mod xml;
@@ -0,0 +1,164 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use clap::{Args, Parser, Subcommand};
+use fastreach_core::{builder::GraphBuilder, graph::Graph, header::Metadata};
+use fastreach_import::{error::ImportError, gtfs, netex, stations};
+
+/// ratio of the walked to the direct distance between stations
+const DETOUR_DEFAULT: f32 = 1.3;
+
+/// Converts timetables into the graph format read by fastreach.
+#[derive(Parser)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Imports a zipped GTFS feed
+    Gtfs {
+        feed: PathBuf,
+        #[command(flatten)]
+        footpaths: Footpaths,
+        #[command(flatten)]
+        output: Output,
+    },
+    /// Imports NeTEx from a XML file, a directory or a zip archive of XML files
+    Netex {
+        input: PathBuf,
+        #[command(flatten)]
+        footpaths: Footpaths,
+        #[command(flatten)]
+        output: Output,
+    },
+    /// Adds walks between nearby stations to an existing graph
+    Footpaths {
+        graph: PathBuf,
+        /// connect stations within this distance in m
+        #[arg(long, default_value_t = 400.0)]
+        radius: f32,
+        /// ratio of the walked to the direct distance
+        #[arg(long, default_value_t = DETOUR_DEFAULT)]
+        detour: f32,
+        #[command(flatten)]
+        output: Output,
+    },
+}
+
+#[derive(Args)]
+struct Footpaths {
+    /// connect stations within this distance in m by walks
+    #[arg(long)]
+    footpath_radius: Option<f32>,
+    /// ratio of the walked to the direct distance
+    #[arg(long, default_value_t = DETOUR_DEFAULT)]
+    detour: f32,
+}
+
+impl Footpaths {
+    fn add(&self, builder: &mut GraphBuilder) -> Result<(), ImportError> {
+        if let Some(radius) = self.footpath_radius {
+            add_footpaths(builder, radius, self.detour)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct Output {
+    /// path of the written graph
+    #[arg(short, long, default_value = "graph.bin")]
+    output: PathBuf,
+    /// path of the written station list used by the UI
+    #[arg(short, long, default_value = "nodes-v1.json")]
+    nodes: PathBuf,
+}
+
+fn main() {
+    if let Err(err) = run(Cli::parse()) {
+        eprintln!("error: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<(), ImportError> {
+    match cli.command {
+        Command::Gtfs {
+            feed,
+            footpaths,
+            output,
+        } => {
+            let file = BufReader::new(File::open(&feed)?);
+            let (builder, skipped) = gtfs::import(file)?;
+            report_skipped(skipped);
+            let mut builder = builder.with_source(&format!("GTFS {}", name(&feed)));
+            footpaths.add(&mut builder)?;
+            write(&builder, &output)
+        }
+        Command::Netex {
+            input,
+            footpaths,
+            output,
+        } => {
+            let (builder, skipped) = netex::import(&input)?;
+            report_skipped(skipped);
+            let mut builder = builder.with_source(&format!("NeTEx {}", name(&input)));
+            footpaths.add(&mut builder)?;
+            write(&builder, &output)
+        }
+        Command::Footpaths {
+            graph,
+            radius,
+            detour,
+            output,
+        } => {
+            let data = std::fs::read(graph)?;
+            let graph = if Metadata::has_header(&data) {
+                Graph::from_slice(&data)
+            } else {
+                Graph::from_legacy_slice(&data)
+            }?;
+            let mut builder = GraphBuilder::from_graph(&graph)?;
+            add_footpaths(&mut builder, radius, detour)?;
+            write(&builder, &output)
+        }
+    }
+}
+
+fn add_footpaths(builder: &mut GraphBuilder, radius: f32, detour: f32) -> Result<(), ImportError> {
+    let count = builder.add_footpaths(radius, detour)?;
+    println!("added {count} walks between stations within {radius} m");
+    Ok(())
+}
+
+fn report_skipped(skipped: usize) {
+    if skipped > 0 {
+        println!("skipped {skipped} trips with decreasing times");
+    }
+}
+
+fn name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn write(builder: &GraphBuilder, output: &Output) -> Result<(), ImportError> {
+    let data = builder.to_bytes()?;
+    std::fs::write(&output.output, &data)?;
+    // reading the graph back ensures it can be served
+    let graph = Graph::from_slice(&data)?;
+    stations::write(&graph, BufWriter::new(File::create(&output.nodes)?))?;
+    println!(
+        "wrote {} stations to {} and {}",
+        graph.nodes.len(),
+        output.output.display(),
+        output.nodes.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,687 @@
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Datelike, Duration, NaiveDate};
+use fastreach_core::{
+    builder::{GraphBuilder, PeriodId},
+    error::BuildError,
+};
+use fnv::FnvHashMap;
+use zip::ZipArchive;
+
+use crate::{
+    error::ImportError,
+    timetable::{self, StopEvent},
+    xml::{self, Element},
+};
+
+const NETEX: &str = "NeTEx";
+/// Elements read before the service journeys.
+const NETWORK_ELEMENTS: &[&str] = &[
+    "DefaultLocale",
+    "StopPlace",
+    "PassengerStopAssignment",
+    "ServiceJourneyPattern",
+    "JourneyPattern",
+    "DayType",
+    "DayTypeAssignment",
+    "OperatingPeriod",
+    "UicOperatingPeriod",
+    "SiteConnection",
+];
+const JOURNEY_ELEMENTS: &[&str] = &["ServiceJourney"];
+/// Bits of all days of the week starting with monday.
+const ALL_DAYS: u8 = 0x7F;
+
+/// XML documents of the input, which can be read several times.
+enum Input {
+    Files(Vec<PathBuf>),
+    Zip(PathBuf),
+}
+
+impl Input {
+    fn open(path: &Path) -> Result<Self, ImportError> {
+        if path.is_dir() {
+            let mut files = Vec::new();
+            for entry in std::fs::read_dir(path)? {
+                let file = entry?.path();
+                if is_xml(&file) {
+                    files.push(file);
+                }
+            }
+            files.sort();
+            return Ok(Self::Files(files));
+        }
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+        {
+            return Ok(Self::Zip(path.to_owned()));
+        }
+        Ok(Self::Files(vec![path.to_owned()]))
+    }
+
+    /// Calls `f` for every element with one of the names in all documents.
+    fn for_each_element(
+        &self,
+        names: &[&str],
+        mut f: impl FnMut(Element) -> Result<(), ImportError>,
+    ) -> Result<(), ImportError> {
+        match self {
+            Self::Files(files) => {
+                for file in files {
+                    let reader = BufReader::new(File::open(file)?);
+                    xml::for_each_element(reader, names, &mut f)?;
+                }
+            }
+            Self::Zip(path) => {
+                let mut archive = ZipArchive::new(BufReader::new(File::open(path)?))?;
+                for idx in 0..archive.len() {
+                    let entry = archive.by_index(idx)?;
+                    if entry.is_file() && is_xml(Path::new(entry.name())) {
+                        xml::for_each_element(BufReader::new(entry), names, &mut f)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_xml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("xml"))
+}
+
+/// Operating days assigned to a day type.
+struct Assignment {
+    day_type: String,
+    period: Option<String>,
+    date: Option<NaiveDate>,
+    available: bool,
+}
+
+/// Everything besides the service journeys.
+#[derive(Default)]
+struct Network {
+    timezone: Option<chrono_tz::Tz>,
+    /// node of stop places and their quays
+    stations: FnvHashMap<String, u32>,
+    /// stop place or quay of scheduled stop points
+    assignments: FnvHashMap<String, String>,
+    /// scheduled stop point of the stop points of journey patterns
+    points: FnvHashMap<String, String>,
+    /// days of the week a day type applies to
+    day_types: FnvHashMap<String, u8>,
+    day_type_assignments: Vec<Assignment>,
+    periods: FnvHashMap<String, BTreeSet<NaiveDate>>,
+    /// stop places or quays connected by a walk of the duration
+    connections: Vec<(String, String, Duration)>,
+}
+
+impl Network {
+    fn read(&mut self, element: &Element, builder: &mut GraphBuilder) -> Result<(), ImportError> {
+        match element.name.as_str() {
+            "DefaultLocale" => {
+                if let (None, Some(timezone)) = (self.timezone, element.text(&["TimeZone"])) {
+                    self.timezone = Some(
+                        timezone
+                            .parse()
+                            .map_err(|_| invalid("TimeZone", timezone))?,
+                    );
+                }
+            }
+            "StopPlace" => self.read_stop_place(element, builder)?,
+            "PassengerStopAssignment" => {
+                let point = element.reference(&["ScheduledStopPointRef"]);
+                let place = element
+                    .reference(&["QuayRef"])
+                    .or_else(|| element.reference(&["StopPlaceRef"]));
+                if let (Some(point), Some(place)) = (point, place) {
+                    self.assignments.insert(point.to_owned(), place.to_owned());
+                }
+            }
+            "ServiceJourneyPattern" | "JourneyPattern" => {
+                let Some(points) = element.find(&["pointsInSequence"]) else {
+                    return Ok(());
+                };
+                for point in points.children("StopPointInJourneyPattern") {
+                    let id = point.attribute("id");
+                    let scheduled = point.reference(&["ScheduledStopPointRef"]);
+                    if let (Some(id), Some(scheduled)) = (id, scheduled) {
+                        self.points.insert(id.to_owned(), scheduled.to_owned());
+                    }
+                }
+            }
+            "DayType" => {
+                let Some(id) = element.attribute("id") else {
+                    return Ok(());
+                };
+                let days = element
+                    .text(&["properties", "PropertyOfDay", "DaysOfWeek"])
+                    .map_or(Ok(ALL_DAYS), parse_days_of_week)?;
+                self.day_types.insert(id.to_owned(), days);
+            }
+            "DayTypeAssignment" => {
+                let Some(day_type) = element.reference(&["DayTypeRef"]) else {
+                    return Ok(());
+                };
+                self.day_type_assignments.push(Assignment {
+                    day_type: day_type.to_owned(),
+                    period: element
+                        .reference(&["OperatingPeriodRef"])
+                        .or_else(|| element.reference(&["UicOperatingPeriodRef"]))
+                        .map(str::to_owned),
+                    date: element.text(&["Date"]).map(parse_date).transpose()?,
+                    available: element.text(&["isAvailable"]) != Some("false"),
+                });
+            }
+            "OperatingPeriod" | "UicOperatingPeriod" => self.read_operating_period(element)?,
+            "SiteConnection" => {
+                let place = |end| {
+                    element
+                        .reference(&[end, "QuayRef"])
+                        .or_else(|| element.reference(&[end, "StopPlaceRef"]))
+                };
+                let duration = element.text(&["WalkTransferDuration", "DefaultDuration"]);
+                if let (Some(from), Some(to), Some(duration)) =
+                    (place("From"), place("To"), duration)
+                {
+                    self.connections.push((
+                        from.to_owned(),
+                        to.to_owned(),
+                        parse_duration(duration)?,
+                    ));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn read_stop_place(
+        &mut self,
+        element: &Element,
+        builder: &mut GraphBuilder,
+    ) -> Result<(), ImportError> {
+        let Some(id) = element.attribute("id") else {
+            return Ok(());
+        };
+        // exports repeat shared stop places in every file
+        if self.stations.contains_key(id) {
+            return Ok(());
+        }
+        let quays: Vec<_> = element
+            .find(&["quays"])
+            .map(|quays| quays.children("Quay").collect())
+            .unwrap_or_default();
+        // stop places without centroid are located at their first quay
+        let Some((lat, lon)) = std::iter::once(element)
+            .chain(quays.iter().copied())
+            .find_map(location)
+        else {
+            return Ok(());
+        };
+        let name = element.text(&["Name"]).unwrap_or_default();
+        let node = builder.add_node(timetable::node_id(id), lat, lon, name)?;
+        self.stations.insert(id.to_owned(), node);
+        for quay in quays {
+            if let Some(quay) = quay.attribute("id") {
+                self.stations.insert(quay.to_owned(), node);
+            }
+        }
+        Ok(())
+    }
+
+    fn read_operating_period(&mut self, element: &Element) -> Result<(), ImportError> {
+        let Some(id) = element.attribute("id") else {
+            return Ok(());
+        };
+        let (Some(from), Some(to)) = (element.text(&["FromDate"]), element.text(&["ToDate"]))
+        else {
+            return Ok(());
+        };
+        let (from, to) = (parse_date(from)?, parse_date(to)?);
+        let days = from.iter_days().take_while(|day| *day <= to);
+        let days = match element.text(&["ValidDayBits"]) {
+            Some(bits) => days
+                .zip(bits.chars())
+                .filter(|(_, bit)| *bit == '1')
+                .map(|(day, _)| day)
+                .collect(),
+            None => days.collect(),
+        };
+        self.periods.insert(id.to_owned(), days);
+        Ok(())
+    }
+
+    /// Operating days of every day type.
+    fn operating_days(&self) -> FnvHashMap<&str, BTreeSet<NaiveDate>> {
+        let mut days = FnvHashMap::<&str, BTreeSet<NaiveDate>>::default();
+        // exceptions are applied after the regular days
+        let (available, unavailable): (Vec<_>, Vec<_>) =
+            self.day_type_assignments.iter().partition(|a| a.available);
+        for assignment in available.into_iter().chain(unavailable) {
+            let weekdays = self
+                .day_types
+                .get(&assignment.day_type)
+                .copied()
+                .unwrap_or(ALL_DAYS);
+            let assigned = assignment
+                .period
+                .as_ref()
+                .and_then(|period| self.periods.get(period))
+                .into_iter()
+                .flatten()
+                .copied()
+                .filter(|day| weekdays & (1 << day.weekday().num_days_from_monday()) != 0)
+                .chain(assignment.date);
+            let days = days.entry(&assignment.day_type).or_default();
+            for day in assigned {
+                if assignment.available {
+                    days.insert(day);
+                } else {
+                    days.remove(&day);
+                }
+            }
+        }
+        days
+    }
+
+    /// Adds walks between stations and change times within stations.
+    /// Connections of places, which cannot be located, are ignored.
+    fn add_connections(&self, builder: &mut GraphBuilder) -> Result<(), ImportError> {
+        let mut change_times = FnvHashMap::<u32, Duration>::default();
+        for (from, to, duration) in &self.connections {
+            let (Some(from), Some(to)) = (self.stations.get(from), self.stations.get(to)) else {
+                continue;
+            };
+            if from == to {
+                let change = change_times.entry(*from).or_default();
+                *change = (*change).max(*duration);
+            } else {
+                builder.add_walk(*from, *to, *duration)?;
+            }
+        }
+        for (node, duration) in change_times {
+            builder.set_change_time(node, duration)?;
+        }
+        Ok(())
+    }
+
+    /// Node of a stop point of a journey pattern.
+    fn node(&self, point: &str) -> Option<u32> {
+        let scheduled = self.points.get(point)?;
+        let place = self.assignments.get(scheduled)?;
+        self.stations.get(place).copied()
+    }
+}
+
+/// Converts `NeTEx` XML into a graph.
+///
+/// The input is a XML file, a directory or a zip archive of XML files. It is streamed
+/// twice, first for stop places, journey patterns and calendars and then for the service
+/// journeys, so only one journey is kept in memory at a time. Quays are merged into
+/// their stop place and stops, which cannot be located, are passed without journey.
+/// Site connections become walks or, within a stop place, change times. Service
+/// journeys with decreasing times are skipped and their number is returned with the graph.
+/// # Errors
+/// If the input cannot be read or contains invalid values.
+pub fn import(path: &Path) -> Result<(GraphBuilder, usize), ImportError> {
+    let input = Input::open(path)?;
+    let mut builder = GraphBuilder::new();
+    let mut network = Network::default();
+    input.for_each_element(NETWORK_ELEMENTS, |element| {
+        network.read(&element, &mut builder)
+    })?;
+    if let Some(timezone) = network.timezone {
+        builder = builder.with_timezone(timezone);
+    }
+    network.add_connections(&mut builder)?;
+    let operating_days = network.operating_days();
+    // journeys of the same day types share their operating period
+    let mut periods = FnvHashMap::<Vec<String>, Option<PeriodId>>::default();
+    let mut trip = 0;
+    let mut skipped = 0;
+    input.for_each_element(JOURNEY_ELEMENTS, |journey| {
+        let day_types: Vec<String> = journey
+            .find(&["dayTypes"])
+            .map(|types| {
+                types
+                    .children("DayTypeRef")
+                    .filter_map(|day_type| day_type.attribute("ref"))
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let period = if let Some(period) = periods.get(&day_types) {
+            *period
+        } else {
+            let days = day_types
+                .iter()
+                .filter_map(|day_type| operating_days.get(day_type.as_str()))
+                .flatten()
+                .copied()
+                .collect();
+            let period = timetable::add_period(&mut builder, &days)?;
+            periods.insert(day_types, period);
+            period
+        };
+        // journeys without days of operation are never used
+        let Some(period) = period else {
+            return Ok(());
+        };
+        let events = read_passing_times(&journey, &network)?;
+        // national exports contain a few broken journeys, which must not prevent the import
+        if !timetable::add_trip(&mut builder, &events, period, trip)? {
+            skipped += 1;
+            return Ok(());
+        }
+        trip = trip
+            .checked_add(1)
+            .ok_or(BuildError::TooLarge { what: "trips" })?;
+        Ok(())
+    })?;
+    Ok((builder, skipped))
+}
+
+fn read_passing_times(journey: &Element, network: &Network) -> Result<Vec<StopEvent>, ImportError> {
+    let Some(times) = journey.find(&["passingTimes"]) else {
+        return Ok(Vec::new());
+    };
+    let mut events = Vec::new();
+    for time in times.children("TimetabledPassingTime") {
+        let Some(node) = time
+            .reference(&["StopPointInJourneyPatternRef"])
+            .and_then(|point| network.node(point))
+        else {
+            continue;
+        };
+        events.push(StopEvent {
+            node,
+            arrival: parse_time(time, "ArrivalTime", "ArrivalDayOffset")?,
+            departure: parse_time(time, "DepartureTime", "DepartureDayOffset")?,
+        });
+    }
+    Ok(events)
+}
+
+fn invalid(field: &'static str, value: &str) -> ImportError {
+    ImportError::Invalid {
+        file: NETEX,
+        field,
+        value: value.to_owned(),
+    }
+}
+
+/// Parses the date of a xsd:date or xsd:dateTime.
+fn parse_date(value: &str) -> Result<NaiveDate, ImportError> {
+    value
+        .get(..10)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .ok_or_else(|| invalid("date", value))
+}
+
+/// Minutes of the service day of a xsd:time and its day offset.
+fn parse_time(
+    element: &Element,
+    field: &'static str,
+    offset: &'static str,
+) -> Result<Option<u16>, ImportError> {
+    let Some(value) = element.text(&[field]) else {
+        return Ok(None);
+    };
+    let mut parts = value.split(':').map(str::parse::<u16>);
+    let (Some(Ok(hours)), Some(Ok(minutes))) = (parts.next(), parts.next()) else {
+        return Err(invalid(field, value));
+    };
+    let days = match element.text(&[offset]) {
+        Some(days) => days.parse::<u16>().map_err(|_| invalid(offset, days))?,
+        None => 0,
+    };
+    let minutes = days
+        .checked_mul(24)
+        .and_then(|hours_before| hours_before.checked_add(hours))
+        .and_then(|hours| hours.checked_mul(60))
+        .and_then(|hours| hours.checked_add(minutes))
+        .filter(|_| minutes < 60)
+        .ok_or_else(|| invalid(field, value))?;
+    Ok(Some(minutes))
+}
+
+/// Parses a xsd:duration like "PT2M30S" in whole days, hours, minutes and seconds.
+fn parse_duration(value: &str) -> Result<Duration, ImportError> {
+    let invalid = || invalid("DefaultDuration", value);
+    let mut rest = value.strip_prefix('P').ok_or_else(invalid)?;
+    let mut time = false;
+    let mut seconds = 0;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('T') {
+            time = true;
+            rest = after;
+            continue;
+        }
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let number: i64 = rest[..end].parse().map_err(|_| invalid())?;
+        let factor = match (time, rest.as_bytes()[end]) {
+            (false, b'D') => 86_400,
+            (true, b'H') => 3600,
+            (true, b'M') => 60,
+            (true, b'S') => 1,
+            _ => return Err(invalid()),
+        };
+        seconds += number * factor;
+        rest = &rest[end + 1..];
+    }
+    Ok(Duration::seconds(seconds))
+}
+
+/// Parses a list of days like "Monday Tuesday" or "Weekdays" into bits starting with monday.
+fn parse_days_of_week(value: &str) -> Result<u8, ImportError> {
+    let mut days = 0;
+    for day in value.split_whitespace() {
+        days |= match day {
+            "Monday" => 1,
+            "Tuesday" => 1 << 1,
+            "Wednesday" => 1 << 2,
+            "Thursday" => 1 << 3,
+            "Friday" => 1 << 4,
+            "Saturday" => 1 << 5,
+            "Sunday" => 1 << 6,
+            "Weekdays" => 0x1F,
+            "Weekend" => 0x60,
+            "Everyday" => ALL_DAYS,
+            _ => return Err(invalid("DaysOfWeek", value)),
+        };
+    }
+    Ok(days)
+}
+
+/// Latitude and longitude of the centroid.
+fn location(element: &Element) -> Option<(f32, f32)> {
+    let location = element.find(&["Centroid", "Location"])?;
+    let lat = location.text(&["Latitude"])?.parse().ok()?;
+    let lon = location.text(&["Longitude"])?.parse().ok()?;
+    Some((lat, lon))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use fastreach_core::graph::Graph;
+
+    use super::{import, parse_duration};
+    use crate::testing::{date, edge, node};
+
+    const DOCUMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<PublicationDelivery xmlns="http://www.netex.org.uk/netex">
+  <dataObjects>
+    <CompositeFrame id="CF">
+      <FrameDefaults>
+        <DefaultLocale><TimeZone>Europe/Berlin</TimeZone></DefaultLocale>
+      </FrameDefaults>
+      <frames>
+        <SiteFrame id="SF">
+          <stopPlaces>
+            <StopPlace id="SP1">
+              <Name>First</Name>
+              <quays>
+                <Quay id="Q1">
+                  <Centroid><Location><Longitude>13.4</Longitude><Latitude>52.5</Latitude></Location></Centroid>
+                </Quay>
+              </quays>
+            </StopPlace>
+            <StopPlace id="SP2">
+              <Name>Second</Name>
+              <Centroid><Location><Longitude>13.5</Longitude><Latitude>52.6</Latitude></Location></Centroid>
+            </StopPlace>
+            <StopPlace id="SP3">
+              <Name>Third</Name>
+              <Centroid><Location><Longitude>13.501</Longitude><Latitude>52.601</Latitude></Location></Centroid>
+            </StopPlace>
+          </stopPlaces>
+          <connections>
+            <SiteConnection id="C1">
+              <WalkTransferDuration><DefaultDuration>PT2M</DefaultDuration></WalkTransferDuration>
+              <From><StopPlaceRef ref="SP2"/></From>
+              <To><StopPlaceRef ref="SP3"/></To>
+            </SiteConnection>
+            <SiteConnection id="C2">
+              <WalkTransferDuration><DefaultDuration>PT5M</DefaultDuration></WalkTransferDuration>
+              <From><StopPlaceRef ref="SP1"/><QuayRef ref="Q1"/></From>
+              <To><StopPlaceRef ref="SP1"/></To>
+            </SiteConnection>
+          </connections>
+        </SiteFrame>
+        <ServiceFrame id="SEF">
+          <stopAssignments>
+            <PassengerStopAssignment id="A1" order="1">
+              <ScheduledStopPointRef ref="SSP1"/><QuayRef ref="Q1"/>
+            </PassengerStopAssignment>
+            <PassengerStopAssignment id="A2" order="1">
+              <ScheduledStopPointRef ref="SSP2"/><StopPlaceRef ref="SP2"/>
+            </PassengerStopAssignment>
+          </stopAssignments>
+          <journeyPatterns>
+            <ServiceJourneyPattern id="OUT">
+              <pointsInSequence>
+                <StopPointInJourneyPattern id="OUT1" order="1"><ScheduledStopPointRef ref="SSP1"/></StopPointInJourneyPattern>
+                <StopPointInJourneyPattern id="OUT2" order="2"><ScheduledStopPointRef ref="SSP2"/></StopPointInJourneyPattern>
+              </pointsInSequence>
+            </ServiceJourneyPattern>
+            <ServiceJourneyPattern id="BACK">
+              <pointsInSequence>
+                <StopPointInJourneyPattern id="BACK1" order="1"><ScheduledStopPointRef ref="SSP2"/></StopPointInJourneyPattern>
+                <StopPointInJourneyPattern id="BACK2" order="2"><ScheduledStopPointRef ref="SSP1"/></StopPointInJourneyPattern>
+              </pointsInSequence>
+            </ServiceJourneyPattern>
+          </journeyPatterns>
+        </ServiceFrame>
+        <ServiceCalendarFrame id="SCF">
+          <dayTypes>
+            <DayType id="WD">
+              <properties><PropertyOfDay><DaysOfWeek>Weekdays</DaysOfWeek></PropertyOfDay></properties>
+            </DayType>
+            <DayType id="X"/>
+          </dayTypes>
+          <operatingPeriods>
+            <OperatingPeriod id="JAN">
+              <FromDate>2024-01-01T00:00:00</FromDate><ToDate>2024-01-31T00:00:00</ToDate>
+            </OperatingPeriod>
+          </operatingPeriods>
+          <dayTypeAssignments>
+            <DayTypeAssignment id="DTA1" order="1">
+              <OperatingPeriodRef ref="JAN"/><DayTypeRef ref="WD"/>
+            </DayTypeAssignment>
+            <DayTypeAssignment id="DTA2" order="2">
+              <Date>2024-01-08</Date><DayTypeRef ref="WD"/><isAvailable>false</isAvailable>
+            </DayTypeAssignment>
+            <DayTypeAssignment id="DTA3" order="3">
+              <Date>2024-01-13</Date><DayTypeRef ref="X"/>
+            </DayTypeAssignment>
+          </dayTypeAssignments>
+        </ServiceCalendarFrame>
+        <TimetableFrame id="TF">
+          <vehicleJourneys>
+            <ServiceJourney id="SJ1">
+              <dayTypes><DayTypeRef ref="WD"/></dayTypes>
+              <passingTimes>
+                <TimetabledPassingTime><StopPointInJourneyPatternRef ref="OUT1"/><DepartureTime>08:00:00</DepartureTime></TimetabledPassingTime>
+                <TimetabledPassingTime><StopPointInJourneyPatternRef ref="OUT2"/><ArrivalTime>08:10:00</ArrivalTime></TimetabledPassingTime>
+              </passingTimes>
+            </ServiceJourney>
+            <ServiceJourney id="SJ2">
+              <dayTypes><DayTypeRef ref="X"/></dayTypes>
+              <passingTimes>
+                <TimetabledPassingTime><StopPointInJourneyPatternRef ref="BACK1"/><DepartureTime>23:50:00</DepartureTime></TimetabledPassingTime>
+                <TimetabledPassingTime><StopPointInJourneyPatternRef ref="BACK2"/><ArrivalTime>00:20:00</ArrivalTime><ArrivalDayOffset>1</ArrivalDayOffset></TimetabledPassingTime>
+              </passingTimes>
+            </ServiceJourney>
+            <ServiceJourney id="BAD">
+              <dayTypes><DayTypeRef ref="WD"/></dayTypes>
+              <passingTimes>
+                <TimetabledPassingTime><StopPointInJourneyPatternRef ref="OUT1"/><DepartureTime>10:00:00</DepartureTime></TimetabledPassingTime>
+                <TimetabledPassingTime><StopPointInJourneyPatternRef ref="OUT2"/><ArrivalTime>09:50:00</ArrivalTime></TimetabledPassingTime>
+              </passingTimes>
+            </ServiceJourney>
+          </vehicleJourneys>
+        </TimetableFrame>
+      </frames>
+    </CompositeFrame>
+  </dataObjects>
+</PublicationDelivery>
+"#;
+
+    #[test]
+    fn minimal_document() {
+        let path = std::env::temp_dir().join(format!("fastreach-netex-{}.xml", std::process::id()));
+        std::fs::write(&path, DOCUMENT).unwrap();
+        let imported = import(&path);
+        std::fs::remove_file(&path).unwrap();
+        let (builder, skipped) = imported.unwrap();
+        assert_eq!(skipped, 1);
+        let data = builder.to_bytes().unwrap();
+        let graph = Graph::from_slice(&data).unwrap();
+        assert_eq!(graph.timezone(), chrono_tz::Europe::Berlin);
+        // the quay is merged into its stop place, which is located at the quay
+        assert_eq!(graph.nodes.len(), 3);
+        assert!(!graph.ids.contains_key(&crate::timetable::node_id("Q1")));
+        assert!((graph.nodes[node(&graph, "SP1")].lat() - 52.5).abs() < 1e-6);
+
+        // removed by an unavailable assignment and the skipped journey adds nothing
+        let first = edge(&graph, "SP1", "SP2");
+        assert!(first.departures_on(date(8)).is_empty());
+        let departures = first.departures_on(date(9));
+        assert_eq!(departures.len(), 1);
+        assert_eq!(departures[0].1, date(9).and_hms_opt(8, 0, 0).unwrap());
+
+        // assigned to a single date and arriving on the next day
+        let late = edge(&graph, "SP2", "SP1");
+        let journey = late.journeys().next().unwrap();
+        assert_eq!((journey.departure(), journey.arrival()), (1430, 1460));
+        let departures = late.departures_on(date(13));
+        assert_eq!(departures.len(), 1);
+        assert_eq!(departures[0].2, date(14).and_hms_opt(0, 20, 0).unwrap());
+
+        // connections between stop places are walks, within a stop place change times
+        assert_eq!(edge(&graph, "SP2", "SP3").walk(), 120);
+        let station = u32::try_from(node(&graph, "SP1")).unwrap();
+        assert_eq!(graph.change_times[&station], Duration::minutes(5));
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("PT2M30S").unwrap(), Duration::seconds(150));
+        assert_eq!(parse_duration("P1DT1H").unwrap(), Duration::hours(25));
+        assert_eq!(parse_duration("PT0S").unwrap(), Duration::zero());
+        assert!(parse_duration("PT2").is_err());
+        assert!(parse_duration("P2M").is_err());
+        assert!(parse_duration("T2M").is_err());
+    }
+}
@@ -0,0 +1,32 @@
+use std::io::Write;
+
+use fastreach_core::graph::Graph;
+
+use crate::error::ImportError;
+
+/// Station as listed in nodes-v1.json, which is read by the UI.
+#[derive(serde_derive::Serialize)]
+struct Station<'a> {
+    name: &'a str,
+    /// longitude and latitude
+    coords: [f32; 2],
+    // JS cannot deal with large integers in JSON
+    id: String,
+}
+
+/// Writes all nodes of the graph as station list.
+/// # Errors
+/// If writing fails.
+pub fn write<W: Write>(graph: &Graph<'_>, writer: W) -> Result<(), ImportError> {
+    let stations: Vec<_> = graph
+        .nodes
+        .iter()
+        .map(|node| Station {
+            name: node.name().unwrap_or_default(),
+            coords: [node.lon(), node.lat()],
+            id: node.id().to_string(),
+        })
+        .collect();
+    serde_json::to_writer(writer, &stations)?;
+    Ok(())
+}
@@ -0,0 +1,27 @@
+//! Helpers shared by the unit tests.
+
+use chrono::NaiveDate;
+use fastreach_core::graph::{Edge, Graph};
+
+use crate::timetable;
+
+/// Day of January 2024, whose 8th is a Monday.
+pub fn date(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 1, day).unwrap()
+}
+
+/// Index of the node imported for the stop or station id.
+pub fn node(graph: &Graph<'_>, id: &str) -> usize {
+    graph.ids[&timetable::node_id(id)]
+}
+
+/// Edge between the nodes imported for the ids.
+pub fn edge<'a>(graph: &Graph<'a>, from: &str, to: &str) -> Edge<'a> {
+    let end = u32::try_from(node(graph, to)).unwrap();
+    graph.nodes[node(graph, from)]
+        .outgoing
+        .iter()
+        .find(|edge| edge.end() == end)
+        .copied()
+        .unwrap()
+}
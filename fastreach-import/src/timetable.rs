@@ -0,0 +1,73 @@
+use std::{collections::BTreeSet, hash::Hasher};
+
+use chrono::{Duration, NaiveDate};
+use fastreach_core::{
+    builder::{GraphBuilder, PeriodId},
+    error::BuildError,
+    graph::Journey,
+};
+use fnv::FnvHasher;
+
+/// An operating period stores one bit per day in at most 255 bytes.
+pub(crate) const MAX_PERIOD_DAYS: i64 = 255 * 8;
+
+/// Stop of a trip with times in minutes of the service day.
+pub(crate) struct StopEvent {
+    pub node: u32,
+    pub arrival: Option<u16>,
+    pub departure: Option<u16>,
+}
+
+/// Ids of the graph are numbers, so ids of the timetable are hashed.
+pub(crate) fn node_id(id: &str) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write(id.as_bytes());
+    hasher.finish()
+}
+
+/// Adds an operating period for the days, which are limited to `MAX_PERIOD_DAYS`
+/// from the first. Returns `None` without days.
+pub(crate) fn add_period(
+    builder: &mut GraphBuilder,
+    days: &BTreeSet<NaiveDate>,
+) -> Result<Option<PeriodId>, BuildError> {
+    let (Some(first), Some(last)) = (days.first().copied(), days.last().copied()) else {
+        return Ok(None);
+    };
+    let last = last.min(first + Duration::days(MAX_PERIOD_DAYS - 1));
+    builder
+        .add_period(first, last, days.range(..=last).copied())
+        .map(Some)
+}
+
+/// Adds a journey for every pair of consecutive timed stops of a trip.
+/// Stops without times are passed and stops within the same station are merged.
+/// Returns false without adding journeys if the times decrease or exceed the format.
+pub(crate) fn add_trip(
+    builder: &mut GraphBuilder,
+    events: &[StopEvent],
+    period: PeriodId,
+    trip: u32,
+) -> Result<bool, BuildError> {
+    let timed: Vec<_> = events
+        .iter()
+        .filter(|event| event.arrival.is_some() || event.departure.is_some())
+        .collect();
+    let mut journeys = Vec::with_capacity(timed.len());
+    for pair in timed.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+        if from.node == to.node {
+            continue;
+        }
+        let departure = from.departure.or(from.arrival).unwrap_or_default();
+        let arrival = to.arrival.or(to.departure).unwrap_or_default();
+        if arrival < departure || arrival > Journey::MAX_MINUTES {
+            return Ok(false);
+        }
+        journeys.push((from.node, to.node, departure, arrival));
+    }
+    for (from, to, departure, arrival) in journeys {
+        builder.add_journey(from, to, departure, arrival, period, Some(trip))?;
+    }
+    Ok(true)
+}
@@ -0,0 +1,117 @@
+use std::io::BufRead;
+
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use crate::error::ImportError;
+
+/// Element of a XML document with namespace prefixes removed.
+#[derive(Debug, Default)]
+pub(crate) struct Element {
+    pub name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    pub children: Vec<Element>,
+}
+
+impl Element {
+    fn new(start: &BytesStart<'_>) -> Result<Self, ImportError> {
+        let mut attributes = Vec::new();
+        for attribute in start.attributes() {
+            let attribute = attribute.map_err(quick_xml::Error::from)?;
+            let key = String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned();
+            attributes.push((key, attribute.unescape_value()?.into_owned()));
+        }
+        Ok(Self {
+            name: String::from_utf8_lossy(start.local_name().as_ref()).into_owned(),
+            attributes,
+            text: String::new(),
+            children: Vec::new(),
+        })
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    /// Follows the names of the path through the children.
+    pub fn find(&self, path: &[&str]) -> Option<&Element> {
+        path.iter().try_fold(self, |element, name| {
+            element.children.iter().find(|child| child.name == *name)
+        })
+    }
+
+    /// Text of the element at the path.
+    pub fn text(&self, path: &[&str]) -> Option<&str> {
+        self.find(path)
+            .map(|element| element.text.as_str())
+            .filter(|text| !text.is_empty())
+    }
+
+    /// Value of the ref attribute of the element at the path.
+    pub fn reference(&self, path: &[&str]) -> Option<&str> {
+        self.find(path)?.attribute("ref")
+    }
+}
+
+/// Streams the document and calls `f` for every element with one of the names.
+/// Only matching elements are kept in memory, so large documents can be read.
+/// Matches within other matches are children of the outer one.
+pub(crate) fn for_each_element<R: BufRead>(
+    reader: R,
+    names: &[&str],
+    mut f: impl FnMut(Element) -> Result<(), ImportError>,
+) -> Result<(), ImportError> {
+    let mut reader = Reader::from_reader(reader);
+    reader.config_mut().trim_text(true);
+    let matches = |start: &BytesStart<'_>| {
+        let name = start.local_name();
+        names.iter().any(|n| n.as_bytes() == name.as_ref())
+    };
+    let mut buf = Vec::new();
+    // open elements of the current match
+    let mut stack: Vec<Element> = Vec::new();
+    loop {
+        let finished = match reader.read_event_into(&mut buf)? {
+            Event::Start(start) if !stack.is_empty() || matches(&start) => {
+                stack.push(Element::new(&start)?);
+                None
+            }
+            Event::Empty(start) if !stack.is_empty() || matches(&start) => {
+                Some(Element::new(&start)?)
+            }
+            Event::Text(text) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&text.unescape()?);
+                }
+                None
+            }
+            Event::CData(data) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&String::from_utf8_lossy(&data));
+                }
+                None
+            }
+            Event::End(_) => stack.pop(),
+            Event::Eof => break,
+            _ => None,
+        };
+        if let Some(element) = finished {
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(element),
+                None => f(element)?,
+            }
+        }
+        buf.clear();
+    }
+    Ok(())
+}